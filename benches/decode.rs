@@ -0,0 +1,230 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Decoding and export of the sample memos in `ppms/`, measured separately
+//! so that regressions can be traced to the stage they're in
+//!
+//! Run with `cargo bench`, or e.g. `cargo bench -- sequential` for one group.
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+
+use {
+  criterion::{
+    BatchSize, BenchmarkId, Criterion, Throughput, criterion_group,
+    criterion_main,
+  },
+  para_cli::{
+    export::{self, IndexedFrame},
+    flipnote::Flipnote,
+    palette::Palette,
+    ppm::PPMParser,
+  },
+  std::{fs, hint::black_box, path::PathBuf},
+};
+
+const MEMOS: [&str; 2] = ["mrjohn", "samplememo_02"];
+
+fn read(name: &str) -> Vec<u8> {
+  fs::read(
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .join("ppms")
+      .join(format!("{name}.ppm")),
+  )
+  .unwrap()
+}
+
+fn load(bytes: &[u8]) -> PPMParser {
+  let mut parser = PPMParser::new(bytes.to_vec());
+
+  parser.load();
+
+  parser
+}
+
+/// Every frame of a memo, as `para <in> gif` decodes them for export
+fn decode_frames(parser: &mut PPMParser) -> Vec<IndexedFrame> {
+  let flipnote: &mut dyn Flipnote = parser;
+
+  flipnote.frames(Palette::DSI, false).flatten().collect()
+}
+
+fn output_path(name: &str) -> String {
+  std::env::temp_dir()
+    .join(format!("para-bench-{name}"))
+    .to_string_lossy()
+    .to_string()
+}
+
+/// Parse the header, metadata, animation header, and sound header
+fn bench_load(c: &mut Criterion) {
+  let mut group = c.benchmark_group("load");
+
+  for name in MEMOS {
+    let bytes = read(name);
+
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_with_input(
+      BenchmarkId::from_parameter(name),
+      &bytes,
+      |b, bytes| {
+        b.iter_batched(
+          || PPMParser::new(bytes.clone()),
+          |mut parser| {
+            parser.load();
+
+            parser
+          },
+          BatchSize::SmallInput,
+        );
+      },
+    );
+  }
+
+  group.finish();
+}
+
+/// Decode the middle frame of a freshly loaded memo, along with the frames
+/// it's based on
+fn bench_random_access(c: &mut Criterion) {
+  let mut group = c.benchmark_group("random_access");
+
+  for name in MEMOS {
+    let bytes = read(name);
+    let index = usize::from(load(&bytes).get_frame_count()) / 2;
+
+    group.bench_function(BenchmarkId::from_parameter(name), |b| {
+      b.iter_batched(
+        || load(&bytes),
+        |mut parser| parser.get_frame_pixels(black_box(index)),
+        BatchSize::SmallInput,
+      );
+    });
+  }
+
+  group.finish();
+}
+
+/// Decode every frame in playback order
+fn bench_sequential(c: &mut Criterion) {
+  let mut group = c.benchmark_group("sequential");
+
+  for name in MEMOS {
+    let bytes = read(name);
+    let frame_count = load(&bytes).get_frame_count();
+
+    group.throughput(Throughput::Elements(u64::from(frame_count)));
+    group.bench_function(BenchmarkId::from_parameter(name), |b| {
+      b.iter_batched(
+        || load(&bytes),
+        |mut parser| {
+          for index in 0..usize::from(frame_count) {
+            black_box(parser.get_frame_pixels(index));
+          }
+        },
+        BatchSize::SmallInput,
+      );
+    });
+  }
+
+  group.finish();
+}
+
+/// Look up the colours of every frame
+fn bench_palette(c: &mut Criterion) {
+  let mut group = c.benchmark_group("palette");
+
+  for name in MEMOS {
+    let mut parser = load(&read(name));
+    let frame_count = parser.get_frame_count();
+
+    group.throughput(Throughput::Elements(u64::from(frame_count)));
+    group.bench_function(BenchmarkId::from_parameter(name), |b| {
+      b.iter(|| {
+        for index in 0..usize::from(frame_count) {
+          black_box(parser.get_frame_palette(index, &Palette::DSI));
+        }
+      });
+    });
+  }
+
+  group.finish();
+}
+
+/// Encode already decoded frames, as `para <in> gif`, `para <in> apng`, and
+/// `para <in> 0 <out>.png` do
+fn bench_export(c: &mut Criterion) {
+  let mut group = c.benchmark_group("export");
+
+  group.sample_size(10);
+
+  for name in MEMOS {
+    let mut parser = load(&read(name));
+    let framerate = parser.get_framerate();
+    let frames = decode_frames(&mut parser);
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    let frame_delay = ((1.0 / framerate) * 100.0) as u16;
+
+    group.bench_function(BenchmarkId::new("gif", name), |b| {
+      let path = output_path(&format!("{name}.gif"));
+
+      b.iter(|| export::write_gif(&path, &frames, frame_delay, false));
+    });
+    group.bench_function(BenchmarkId::new("apng", name), |b| {
+      let path = output_path(&format!("{name}.apng.png"));
+
+      b.iter(|| export::write_apng(&path, &frames, framerate, false));
+    });
+    group.bench_function(BenchmarkId::new("png", name), |b| {
+      let path = output_path(&format!("{name}.png"));
+
+      b.iter(|| frames[0].to_image(false).save(&path).unwrap());
+    });
+  }
+
+  group.finish();
+}
+
+/// Decode and encode every frame, as `para ./ppms/samplememo_02.ppm gif
+/// samplememo_02.gif` does, for comparison with the timings in the README
+fn bench_end_to_end(c: &mut Criterion) {
+  let mut group = c.benchmark_group("end_to_end");
+
+  group.sample_size(10);
+
+  for name in MEMOS {
+    let bytes = read(name);
+    let path = output_path(&format!("{name}.end_to_end.gif"));
+
+    group.bench_function(BenchmarkId::new("gif", name), |b| {
+      b.iter(|| {
+        let mut parser = load(&bytes);
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let frame_delay = ((1.0 / parser.get_framerate()) * 100.0) as u16;
+        let frames = decode_frames(&mut parser);
+
+        export::write_gif(&path, &frames, frame_delay, false);
+      });
+    });
+  }
+
+  group.finish();
+}
+
+criterion_group!(
+  benches,
+  bench_load,
+  bench_random_access,
+  bench_sequential,
+  bench_palette,
+  bench_export,
+  bench_end_to_end
+);
+criterion_main!(benches);
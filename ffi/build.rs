@@ -0,0 +1,34 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Generates `include/para.h` from the functions exported by `src/lib.rs`,
+//! configured by `cbindgen.toml`
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+
+use std::{env, path::PathBuf};
+
+fn main() {
+  let crate_directory = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+
+  println!("cargo:rerun-if-changed=src/lib.rs");
+  println!("cargo:rerun-if-changed=cbindgen.toml");
+
+  cbindgen::Builder::new()
+    .with_crate(&crate_directory)
+    .with_config(
+      cbindgen::Config::from_file(crate_directory.join("cbindgen.toml"))
+        .unwrap(),
+    )
+    .generate()
+    .unwrap()
+    .write_to_file(crate_directory.join("include").join("para.h"));
+}
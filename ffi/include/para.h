@@ -0,0 +1,250 @@
+/* Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+ * SPDX-License-Identifier: MIT */
+
+#ifndef PARA_H
+#define PARA_H
+
+/* Generated from ffi/src/lib.rs by cbindgen; don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The outcome of every call
+typedef enum ParaStatus {
+  PARA_STATUS_OK,
+  // A required pointer was null
+  PARA_STATUS_NULL_POINTER,
+  // The file could not be read
+  PARA_STATUS_IO,
+  // The data isn't a Flipnote of a supported format
+  PARA_STATUS_UNSUPPORTED_FORMAT,
+  // A frame, sound track, or field doesn't exist
+  PARA_STATUS_OUT_OF_RANGE,
+  // The buffer can't hold the result; its length has been written where
+  // asked for
+  PARA_STATUS_BUFFER_TOO_SMALL,
+  // A string argument wasn't valid UTF-8
+  PARA_STATUS_INVALID_UTF8,
+  // A palette wasn't a preset or four colours
+  PARA_STATUS_INVALID_PALETTE,
+  // The decoder panicked; the Flipnote should be freed
+  PARA_STATUS_PANIC,
+} ParaStatus;
+
+// The text fields of a Flipnote's metadata
+typedef enum ParaText {
+  PARA_TEXT_ROOT_AUTHOR_NAME,
+  PARA_TEXT_ROOT_AUTHOR_ID,
+  PARA_TEXT_PARENT_AUTHOR_NAME,
+  PARA_TEXT_PARENT_AUTHOR_ID,
+  PARA_TEXT_CURRENT_AUTHOR_NAME,
+  PARA_TEXT_CURRENT_AUTHOR_ID,
+  PARA_TEXT_PARENT_FILENAME,
+  PARA_TEXT_CURRENT_FILENAME,
+  // Empty for formats where it's the current filename
+  PARA_TEXT_PUBLIC_FILENAME,
+} ParaText;
+
+// A loaded Flipnote, and the palette its frames are drawn with
+typedef struct ParaFlipnote ParaFlipnote;
+
+// The numeric metadata of a Flipnote; text fields are read with
+// [`para_metadata_text`]
+typedef struct ParaMetadata {
+  bool lock;
+  bool looping;
+  uint8_t frame_speed;
+  uint32_t bgm_speed;
+  double bgm_framerate;
+  uint16_t thumb_index;
+  // When the Flipnote was last saved, in seconds since the Unix epoch
+  int64_t timestamp;
+  size_t layer_count;
+} ParaMetadata;
+
+// A colour, as used by [`para_frame_palette`]
+typedef struct ParaColour {
+  uint8_t red;
+  uint8_t green;
+  uint8_t blue;
+} ParaColour;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Load a Flipnote from `length` bytes at `data`, which are copied
+//
+// # Safety
+//
+// `data` must point to `length` readable bytes, and `flipnote` to writable
+// memory for a pointer, which must later be passed to [`para_free`].
+enum ParaStatus para_open_buffer(const uint8_t *data,
+                                 size_t length,
+                                 struct ParaFlipnote **flipnote);
+
+// Load a Flipnote from the file at `path`
+//
+// # Safety
+//
+// `path` must be a NUL-terminated string, and `flipnote` must point to
+// writable memory for a pointer, which must later be passed to
+// [`para_free`].
+enum ParaStatus para_open_path(const char *path, struct ParaFlipnote **flipnote);
+
+// Release a Flipnote; a null pointer is ignored
+//
+// # Safety
+//
+// `flipnote` must have come from [`para_open_buffer`] or [`para_open_path`],
+// and mustn't be used afterwards.
+void para_free(struct ParaFlipnote *flipnote);
+
+// A description of a status, as a static NUL-terminated string
+const char *para_status_message(enum ParaStatus status);
+
+// Frame width and height in pixels
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, and `width` and `height` must point to
+// writable memory.
+enum ParaStatus para_dimensions(const struct ParaFlipnote *flipnote, size_t *width, size_t *height);
+
+// The number of frames
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, and `frame_count` must point to
+// writable memory.
+enum ParaStatus para_frame_count(const struct ParaFlipnote *flipnote, uint16_t *frame_count);
+
+// Frames per second
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, and `framerate` must point to
+// writable memory.
+enum ParaStatus para_framerate(const struct ParaFlipnote *flipnote, double *framerate);
+
+// The numeric metadata fields
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, and `metadata` must point to writable
+// memory.
+enum ParaStatus para_metadata(const struct ParaFlipnote *flipnote, struct ParaMetadata *metadata);
+
+// A text metadata field, as a NUL-terminated string
+//
+// `length` receives the length of the field including the NUL.
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, `buffer` must be null or point to
+// `capacity` writable bytes, and `length` must be null or point to writable
+// memory.
+enum ParaStatus para_metadata_text(const struct ParaFlipnote *flipnote,
+                                   enum ParaText field,
+                                   char *buffer,
+                                   size_t capacity,
+                                   size_t *length);
+
+// Draw frames with a preset, such as `"3ds"`, or with colours given as
+// `"black,white,red,blue"` in hexadecimal, as `para --palette` takes them
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, and `palette` a NUL-terminated string.
+enum ParaStatus para_set_palette(struct ParaFlipnote *flipnote, const char *palette);
+
+// The colours of a frame, indexed by its pixels; the paper comes first
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, `colours` must be null or point to
+// `capacity` writable colours, and `length` must be null or point to
+// writable memory.
+enum ParaStatus para_frame_palette(struct ParaFlipnote *flipnote,
+                                   size_t index,
+                                   struct ParaColour *colours,
+                                   size_t capacity,
+                                   size_t *length);
+
+// A frame as palette indices, row by row, into a buffer of at least width
+// by height bytes
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, and `buffer` must point to `capacity`
+// writable bytes.
+enum ParaStatus para_frame_indexed(struct ParaFlipnote *flipnote,
+                                   size_t index,
+                                   uint8_t *buffer,
+                                   size_t capacity);
+
+// A frame as RGBA pixels, row by row, into a buffer of at least width by
+// height by four bytes; the paper is left transparent when `transparent` is
+// set
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, and `buffer` must point to `capacity`
+// writable bytes.
+enum ParaStatus para_frame_rgba(struct ParaFlipnote *flipnote,
+                                size_t index,
+                                bool transparent,
+                                uint8_t *buffer,
+                                size_t capacity);
+
+// Samples per second of the sound tracks
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, and `sample_rate` must point to
+// writable memory.
+enum ParaStatus para_sample_rate(const struct ParaFlipnote *flipnote, uint32_t *sample_rate);
+
+// The number of sound tracks, whether or not they hold any sound
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, and `count` must point to writable
+// memory.
+enum ParaStatus para_sound_track_count(const struct ParaFlipnote *flipnote, size_t *count);
+
+// The name of a sound track, such as `"bgm"` or `"se1"`, as a
+// NUL-terminated string
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, `buffer` must be null or point to
+// `capacity` writable bytes, and `length` must be null or point to writable
+// memory.
+enum ParaStatus para_sound_track_name(const struct ParaFlipnote *flipnote,
+                                      size_t track,
+                                      char *buffer,
+                                      size_t capacity,
+                                      size_t *length);
+
+// A sound track as signed 16-bit PCM at [`para_sample_rate`]
+//
+// # Safety
+//
+// `flipnote` must be a live Flipnote, `samples` must be null or point to
+// `capacity` writable samples, and `length` must be null or point to
+// writable memory.
+enum ParaStatus para_sound_track(const struct ParaFlipnote *flipnote,
+                                 size_t track,
+                                 int16_t *samples,
+                                 size_t capacity,
+                                 size_t *length);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PARA_H */
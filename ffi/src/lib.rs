@@ -0,0 +1,649 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! A C API over the decoder behind `para`, declared in `include/para.h`
+//!
+//! Every function returns a [`ParaStatus`] rather than unwinding into the
+//! caller; a panic while decoding is caught and reported as
+//! `PARA_STATUS_PANIC`. Functions which fill a variable-length buffer accept a
+//! null buffer to ask for the length they need.
+//!
+//! ```c
+//! ParaFlipnote *flipnote;
+//!
+//! if (para_open_path("samplememo_02.ppm", &flipnote) != PARA_STATUS_OK) {
+//!   return 1;
+//! }
+//!
+//! size_t width, height;
+//!
+//! para_dimensions(flipnote, &width, &height);
+//!
+//! uint8_t *rgba = malloc(width * height * 4);
+//!
+//! para_frame_rgba(flipnote, 0, false, rgba, width * height * 4);
+//! free(rgba);
+//! para_free(flipnote);
+//! ```
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use {
+  para_cli::{export::IndexedFrame, flipnote, palette::Palette},
+  std::{
+    ffi::{CStr, c_char},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+  },
+};
+
+/// The outcome of every call
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParaStatus {
+  Ok,
+  /// A required pointer was null
+  NullPointer,
+  /// The file could not be read
+  Io,
+  /// The data isn't a Flipnote of a supported format
+  UnsupportedFormat,
+  /// A frame, sound track, or field doesn't exist
+  OutOfRange,
+  /// The buffer can't hold the result; its length has been written where
+  /// asked for
+  BufferTooSmall,
+  /// A string argument wasn't valid UTF-8
+  InvalidUtf8,
+  /// A palette wasn't a preset or four colours
+  InvalidPalette,
+  /// The decoder panicked; the Flipnote should be freed
+  Panic,
+}
+
+/// A loaded Flipnote, and the palette its frames are drawn with
+pub struct ParaFlipnote {
+  flipnote: Box<dyn flipnote::Flipnote>,
+  palette:  Palette,
+}
+
+/// A colour, as used by [`para_frame_palette`]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParaColour {
+  pub red:   u8,
+  pub green: u8,
+  pub blue:  u8,
+}
+
+/// The numeric metadata of a Flipnote; text fields are read with
+/// [`para_metadata_text`]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParaMetadata {
+  pub lock:          bool,
+  pub looping:       bool,
+  pub frame_speed:   u8,
+  pub bgm_speed:     u32,
+  pub bgm_framerate: f64,
+  pub thumb_index:   u16,
+  /// When the Flipnote was last saved, in seconds since the Unix epoch
+  pub timestamp:     i64,
+  pub layer_count:   usize,
+}
+
+/// The text fields of a Flipnote's metadata
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParaText {
+  RootAuthorName,
+  RootAuthorId,
+  ParentAuthorName,
+  ParentAuthorId,
+  CurrentAuthorName,
+  CurrentAuthorId,
+  ParentFilename,
+  CurrentFilename,
+  /// Empty for formats where it's the current filename
+  PublicFilename,
+}
+
+/// Run `body`, turning a panic into `PARA_STATUS_PANIC`
+fn guard(body: impl FnOnce() -> Result<(), ParaStatus>) -> ParaStatus {
+  match panic::catch_unwind(AssertUnwindSafe(body)) {
+    Ok(Ok(())) => ParaStatus::Ok,
+    Ok(Err(status)) => status,
+    Err(_) => ParaStatus::Panic,
+  }
+}
+
+/// Borrow a pointer argument, or fail if it's null
+const unsafe fn borrow<'a, T>(pointer: *const T) -> Result<&'a T, ParaStatus> {
+  match unsafe { pointer.as_ref() } {
+    Some(value) => Ok(value),
+    None => Err(ParaStatus::NullPointer),
+  }
+}
+
+/// Write through a pointer argument, or fail if it's null
+const unsafe fn write<T: Copy>(
+  pointer: *mut T,
+  value: T,
+) -> Result<(), ParaStatus> {
+  if pointer.is_null() {
+    return Err(ParaStatus::NullPointer);
+  }
+
+  unsafe { pointer.write(value) };
+
+  Ok(())
+}
+
+/// Copy `values` into a caller's buffer, writing their count to `length`
+/// when it isn't null; a null buffer only asks for the count
+const unsafe fn write_slice<T: Copy>(
+  values: &[T],
+  buffer: *mut T,
+  capacity: usize,
+  length: *mut usize,
+) -> Result<(), ParaStatus> {
+  if !length.is_null() {
+    unsafe { length.write(values.len()) };
+  }
+
+  if buffer.is_null() {
+    return if length.is_null() {
+      Err(ParaStatus::NullPointer)
+    } else {
+      Ok(())
+    };
+  }
+
+  if capacity < values.len() {
+    return Err(ParaStatus::BufferTooSmall);
+  }
+
+  unsafe { slice::from_raw_parts_mut(buffer, values.len()) }
+    .copy_from_slice(values);
+
+  Ok(())
+}
+
+/// Copy a string into a caller's buffer with a terminating NUL, as
+/// [`write_slice`] does
+unsafe fn write_string(
+  value: &str,
+  buffer: *mut c_char,
+  capacity: usize,
+  length: *mut usize,
+) -> Result<(), ParaStatus> {
+  let terminated =
+    value.bytes().chain([0]).map(u8::cast_signed).collect::<Vec<c_char>>();
+
+  unsafe { write_slice(&terminated, buffer, capacity, length) }
+}
+
+/// Borrow a string argument
+unsafe fn read_string<'a>(
+  string: *const c_char,
+) -> Result<&'a str, ParaStatus> {
+  if string.is_null() {
+    return Err(ParaStatus::NullPointer);
+  }
+
+  unsafe { CStr::from_ptr(string) }
+    .to_str()
+    .map_err(|_| ParaStatus::InvalidUtf8)
+}
+
+impl ParaFlipnote {
+  fn from_bytes(bytes: Vec<u8>) -> Result<Self, ParaStatus> {
+    let flipnote =
+      flipnote::from_bytes(bytes).ok_or(ParaStatus::UnsupportedFormat)?;
+    let palette = flipnote.default_palette();
+
+    Ok(Self { flipnote, palette })
+  }
+
+  fn check_frame(&self, index: usize) -> Result<(), ParaStatus> {
+    if index < usize::from(self.flipnote.get_frame_count()) {
+      Ok(())
+    } else {
+      Err(ParaStatus::OutOfRange)
+    }
+  }
+
+  fn pixel_count(&self) -> usize {
+    let (width, height) = self.flipnote.dimensions();
+
+    width * height
+  }
+}
+
+/// Load a Flipnote from `length` bytes at `data`, which are copied
+///
+/// # Safety
+///
+/// `data` must point to `length` readable bytes, and `flipnote` to writable
+/// memory for a pointer, which must later be passed to [`para_free`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_open_buffer(
+  data: *const u8,
+  length: usize,
+  flipnote: *mut *mut ParaFlipnote,
+) -> ParaStatus {
+  guard(|| {
+    if data.is_null() || flipnote.is_null() {
+      return Err(ParaStatus::NullPointer);
+    }
+
+    let bytes = unsafe { slice::from_raw_parts(data, length) }.to_vec();
+    let opened = ParaFlipnote::from_bytes(bytes)?;
+
+    unsafe { write(flipnote, Box::into_raw(Box::new(opened))) }
+  })
+}
+
+/// Load a Flipnote from the file at `path`
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string, and `flipnote` must point to
+/// writable memory for a pointer, which must later be passed to
+/// [`para_free`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_open_path(
+  path: *const c_char,
+  flipnote: *mut *mut ParaFlipnote,
+) -> ParaStatus {
+  guard(|| {
+    if flipnote.is_null() {
+      return Err(ParaStatus::NullPointer);
+    }
+
+    let bytes = std::fs::read(unsafe { read_string(path) }?)
+      .map_err(|_| ParaStatus::Io)?;
+    let opened = ParaFlipnote::from_bytes(bytes)?;
+
+    unsafe { write(flipnote, Box::into_raw(Box::new(opened))) }
+  })
+}
+
+/// Release a Flipnote; a null pointer is ignored
+///
+/// # Safety
+///
+/// `flipnote` must have come from [`para_open_buffer`] or [`para_open_path`],
+/// and mustn't be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_free(flipnote: *mut ParaFlipnote) {
+  if !flipnote.is_null() {
+    drop(panic::catch_unwind(AssertUnwindSafe(|| {
+      drop(unsafe { Box::from_raw(flipnote) });
+    })));
+  }
+}
+
+/// A description of a status, as a static NUL-terminated string
+#[unsafe(no_mangle)]
+pub const extern "C" fn para_status_message(
+  status: ParaStatus,
+) -> *const c_char {
+  match status {
+    ParaStatus::Ok => c"ok",
+    ParaStatus::NullPointer => c"a required pointer was null",
+    ParaStatus::Io => c"the file could not be read",
+    ParaStatus::UnsupportedFormat => c"unsupported format, expected a Flipnote",
+    ParaStatus::OutOfRange => c"no such frame, sound track, or field",
+    ParaStatus::BufferTooSmall => c"the buffer is too small",
+    ParaStatus::InvalidUtf8 => c"a string wasn't valid UTF-8",
+    ParaStatus::InvalidPalette => c"invalid palette",
+    ParaStatus::Panic => c"the decoder panicked",
+  }
+  .as_ptr()
+}
+
+/// Frame width and height in pixels
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, and `width` and `height` must point to
+/// writable memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_dimensions(
+  flipnote: *const ParaFlipnote,
+  width: *mut usize,
+  height: *mut usize,
+) -> ParaStatus {
+  guard(|| {
+    let (frame_width, frame_height) =
+      unsafe { borrow(flipnote) }?.flipnote.dimensions();
+
+    unsafe { write(width, frame_width) }?;
+    unsafe { write(height, frame_height) }
+  })
+}
+
+/// The number of frames
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, and `frame_count` must point to
+/// writable memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_frame_count(
+  flipnote: *const ParaFlipnote,
+  frame_count: *mut u16,
+) -> ParaStatus {
+  guard(|| unsafe {
+    write(frame_count, borrow(flipnote)?.flipnote.get_frame_count())
+  })
+}
+
+/// Frames per second
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, and `framerate` must point to
+/// writable memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_framerate(
+  flipnote: *const ParaFlipnote,
+  framerate: *mut f64,
+) -> ParaStatus {
+  guard(|| unsafe {
+    write(framerate, borrow(flipnote)?.flipnote.get_framerate())
+  })
+}
+
+/// The numeric metadata fields
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, and `metadata` must point to writable
+/// memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_metadata(
+  flipnote: *const ParaFlipnote,
+  metadata: *mut ParaMetadata,
+) -> ParaStatus {
+  guard(|| {
+    let flipnote = &unsafe { borrow(flipnote) }?.flipnote;
+    let fields = flipnote.metadata();
+
+    unsafe {
+      write(metadata, ParaMetadata {
+        lock:          fields.lock,
+        looping:       fields.loop_,
+        frame_speed:   fields.frame_speed,
+        bgm_speed:     fields.bgm_speed,
+        bgm_framerate: fields.bgm_framerate,
+        thumb_index:   flipnote.get_thumb_index(),
+        timestamp:     fields.timestamp.timestamp(),
+        layer_count:   flipnote.layer_count(),
+      })
+    }
+  })
+}
+
+/// A text metadata field, as a NUL-terminated string
+///
+/// `length` receives the length of the field including the NUL.
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, `buffer` must be null or point to
+/// `capacity` writable bytes, and `length` must be null or point to writable
+/// memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_metadata_text(
+  flipnote: *const ParaFlipnote,
+  field: ParaText,
+  buffer: *mut c_char,
+  capacity: usize,
+  length: *mut usize,
+) -> ParaStatus {
+  guard(|| {
+    let metadata = unsafe { borrow(flipnote) }?.flipnote.metadata();
+    let [root, parent, current] = &metadata.authors;
+    let public_filename = metadata.public_filename.clone().unwrap_or_default();
+    let value = match field {
+      ParaText::RootAuthorName => &root.0,
+      ParaText::RootAuthorId => &root.1,
+      ParaText::ParentAuthorName => &parent.0,
+      ParaText::ParentAuthorId => &parent.1,
+      ParaText::CurrentAuthorName => &current.0,
+      ParaText::CurrentAuthorId => &current.1,
+      ParaText::ParentFilename => &metadata.parent_filename,
+      ParaText::CurrentFilename => &metadata.current_filename,
+      ParaText::PublicFilename => &public_filename,
+    };
+
+    unsafe { write_string(value, buffer, capacity, length) }
+  })
+}
+
+/// Draw frames with a preset, such as `"3ds"`, or with colours given as
+/// `"black,white,red,blue"` in hexadecimal, as `para --palette` takes them
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, and `palette` a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_set_palette(
+  flipnote: *mut ParaFlipnote,
+  palette: *const c_char,
+) -> ParaStatus {
+  guard(|| {
+    let palette = Palette::parse(unsafe { read_string(palette) }?)
+      .map_err(|_| ParaStatus::InvalidPalette)?;
+
+    unsafe { flipnote.as_mut() }.ok_or(ParaStatus::NullPointer)?.palette =
+      palette;
+
+    Ok(())
+  })
+}
+
+/// The colours of a frame, indexed by its pixels; the paper comes first
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, `colours` must be null or point to
+/// `capacity` writable colours, and `length` must be null or point to
+/// writable memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_frame_palette(
+  flipnote: *mut ParaFlipnote,
+  index: usize,
+  colours: *mut ParaColour,
+  capacity: usize,
+  length: *mut usize,
+) -> ParaStatus {
+  guard(|| {
+    let flipnote =
+      unsafe { flipnote.as_mut() }.ok_or(ParaStatus::NullPointer)?;
+
+    flipnote.check_frame(index)?;
+
+    let palette = flipnote
+      .flipnote
+      .get_frame_palette(index, &flipnote.palette)
+      .into_iter()
+      .map(|(red, green, blue)| ParaColour { red, green, blue })
+      .collect::<Vec<_>>();
+
+    unsafe { write_slice(&palette, colours, capacity, length) }
+  })
+}
+
+/// A frame as palette indices, row by row, into a buffer of at least width
+/// by height bytes
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, and `buffer` must point to `capacity`
+/// writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_frame_indexed(
+  flipnote: *mut ParaFlipnote,
+  index: usize,
+  buffer: *mut u8,
+  capacity: usize,
+) -> ParaStatus {
+  guard(|| {
+    let flipnote =
+      unsafe { flipnote.as_mut() }.ok_or(ParaStatus::NullPointer)?;
+
+    flipnote.check_frame(index)?;
+
+    if buffer.is_null() {
+      return Err(ParaStatus::NullPointer);
+    }
+
+    if capacity < flipnote.pixel_count() {
+      return Err(ParaStatus::BufferTooSmall);
+    }
+
+    let pixels = flipnote.flipnote.get_frame_pixels(index).concat();
+
+    unsafe { write_slice(&pixels, buffer, capacity, ptr::null_mut()) }
+  })
+}
+
+/// A frame as RGBA pixels, row by row, into a buffer of at least width by
+/// height by four bytes; the paper is left transparent when `transparent` is
+/// set
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, and `buffer` must point to `capacity`
+/// writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_frame_rgba(
+  flipnote: *mut ParaFlipnote,
+  index: usize,
+  transparent: bool,
+  buffer: *mut u8,
+  capacity: usize,
+) -> ParaStatus {
+  guard(|| {
+    let flipnote =
+      unsafe { flipnote.as_mut() }.ok_or(ParaStatus::NullPointer)?;
+
+    flipnote.check_frame(index)?;
+
+    if buffer.is_null() {
+      return Err(ParaStatus::NullPointer);
+    }
+
+    if capacity < flipnote.pixel_count() * 4 {
+      return Err(ParaStatus::BufferTooSmall);
+    }
+
+    let frame = IndexedFrame {
+      pixels:  flipnote.flipnote.get_frame_pixels(index),
+      colours: flipnote.flipnote.get_frame_palette(index, &flipnote.palette),
+    };
+    let rgba = frame.to_image(transparent).into_rgba8().into_raw();
+
+    unsafe { write_slice(&rgba, buffer, capacity, ptr::null_mut()) }
+  })
+}
+
+/// Samples per second of the sound tracks
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, and `sample_rate` must point to
+/// writable memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_sample_rate(
+  flipnote: *const ParaFlipnote,
+  sample_rate: *mut u32,
+) -> ParaStatus {
+  guard(|| unsafe {
+    write(sample_rate, borrow(flipnote)?.flipnote.sample_rate())
+  })
+}
+
+/// The number of sound tracks, whether or not they hold any sound
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, and `count` must point to writable
+/// memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_sound_track_count(
+  flipnote: *const ParaFlipnote,
+  count: *mut usize,
+) -> ParaStatus {
+  guard(|| unsafe {
+    write(count, borrow(flipnote)?.flipnote.sound_tracks().len())
+  })
+}
+
+/// The name of a sound track, such as `"bgm"` or `"se1"`, as a
+/// NUL-terminated string
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, `buffer` must be null or point to
+/// `capacity` writable bytes, and `length` must be null or point to writable
+/// memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_sound_track_name(
+  flipnote: *const ParaFlipnote,
+  track: usize,
+  buffer: *mut c_char,
+  capacity: usize,
+  length: *mut usize,
+) -> ParaStatus {
+  guard(|| {
+    let tracks = unsafe { borrow(flipnote) }?.flipnote.sound_tracks();
+    let name = tracks.get(track).ok_or(ParaStatus::OutOfRange)?.name;
+
+    unsafe { write_string(name, buffer, capacity, length) }
+  })
+}
+
+/// A sound track as signed 16-bit PCM at [`para_sample_rate`]
+///
+/// # Safety
+///
+/// `flipnote` must be a live Flipnote, `samples` must be null or point to
+/// `capacity` writable samples, and `length` must be null or point to
+/// writable memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn para_sound_track(
+  flipnote: *const ParaFlipnote,
+  track: usize,
+  samples: *mut i16,
+  capacity: usize,
+  length: *mut usize,
+) -> ParaStatus {
+  guard(|| {
+    let flipnote = &unsafe { borrow(flipnote) }?.flipnote;
+
+    if track >= flipnote.sound_tracks().len() {
+      return Err(ParaStatus::OutOfRange);
+    }
+
+    unsafe {
+      write_slice(
+        &flipnote.decode_sound_track(track),
+        samples,
+        capacity,
+        length,
+      )
+    }
+  })
+}
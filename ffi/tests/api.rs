@@ -0,0 +1,298 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Calls the C API the way a C caller would, comparing what it returns with
+//! the decoder it wraps
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+
+use {
+  para::*,
+  para_cli::{flipnote, palette::Palette},
+  std::{
+    ffi::{CStr, CString},
+    path::PathBuf,
+    ptr,
+  },
+};
+
+fn memo_path(name: &str) -> PathBuf {
+  PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    .join("..")
+    .join("ppms")
+    .join(format!("{name}.ppm"))
+}
+
+fn open(name: &str) -> *mut ParaFlipnote {
+  let path = CString::new(memo_path(name).to_str().unwrap()).unwrap();
+  let mut flipnote = ptr::null_mut();
+
+  assert_eq!(
+    unsafe { para_open_path(path.as_ptr(), &raw mut flipnote) },
+    ParaStatus::Ok
+  );
+
+  flipnote
+}
+
+fn text(flipnote: *const ParaFlipnote, field: ParaText) -> String {
+  let mut length = 0;
+
+  assert_eq!(
+    unsafe {
+      para_metadata_text(flipnote, field, ptr::null_mut(), 0, &raw mut length)
+    },
+    ParaStatus::Ok
+  );
+
+  let mut buffer = vec![0; length];
+
+  assert_eq!(
+    unsafe {
+      para_metadata_text(
+        flipnote,
+        field,
+        buffer.as_mut_ptr(),
+        buffer.len(),
+        ptr::null_mut(),
+      )
+    },
+    ParaStatus::Ok
+  );
+
+  unsafe { CStr::from_ptr(buffer.as_ptr()) }.to_str().unwrap().to_string()
+}
+
+#[test]
+fn metadata() {
+  let flipnote = open("samplememo_02");
+  let expected = flipnote::open(memo_path("samplememo_02").to_str().unwrap())
+    .unwrap()
+    .metadata();
+  let mut metadata = std::mem::MaybeUninit::uninit();
+  let (mut width, mut height, mut frame_count, mut framerate) = (0, 0, 0, 0.0);
+
+  unsafe {
+    assert_eq!(para_metadata(flipnote, metadata.as_mut_ptr()), ParaStatus::Ok);
+    assert_eq!(
+      para_dimensions(flipnote, &raw mut width, &raw mut height),
+      ParaStatus::Ok
+    );
+    assert_eq!(
+      para_frame_count(flipnote, &raw mut frame_count),
+      ParaStatus::Ok
+    );
+    assert_eq!(para_framerate(flipnote, &raw mut framerate), ParaStatus::Ok);
+  }
+
+  let metadata = unsafe { metadata.assume_init() };
+
+  assert_eq!((width, height, frame_count), (256, 192, 219));
+  assert!((framerate - 12.0).abs() < f64::EPSILON);
+  assert_eq!(metadata.lock, expected.lock);
+  assert_eq!(metadata.frame_speed, expected.frame_speed);
+  assert_eq!(metadata.timestamp, expected.timestamp.timestamp());
+  assert_eq!(
+    text(flipnote, ParaText::CurrentAuthorName),
+    expected.authors[2].0
+  );
+  assert_eq!(
+    text(flipnote, ParaText::CurrentFilename),
+    expected.current_filename
+  );
+
+  unsafe { para_free(flipnote) };
+}
+
+#[test]
+fn frames() {
+  let flipnote = open("mrjohn");
+  let mut expected =
+    flipnote::open(memo_path("mrjohn").to_str().unwrap()).unwrap();
+  let mut indexed = vec![0; 256 * 192];
+  let mut rgba = vec![0; 256 * 192 * 4];
+  let mut colours = [ParaColour { red: 0, green: 0, blue: 0 }; 3];
+  let mut colour_count = 0;
+
+  for index in [0, 5, 1] {
+    unsafe {
+      assert_eq!(
+        para_frame_indexed(
+          flipnote,
+          index,
+          indexed.as_mut_ptr(),
+          indexed.len()
+        ),
+        ParaStatus::Ok
+      );
+      assert_eq!(
+        para_frame_rgba(flipnote, index, false, rgba.as_mut_ptr(), rgba.len()),
+        ParaStatus::Ok
+      );
+      assert_eq!(
+        para_frame_palette(
+          flipnote,
+          index,
+          colours.as_mut_ptr(),
+          colours.len(),
+          &raw mut colour_count,
+        ),
+        ParaStatus::Ok
+      );
+    }
+
+    let palette = expected.get_frame_palette(index, &Palette::DSI);
+
+    assert_eq!(indexed, expected.get_frame_pixels(index).concat());
+    assert_eq!(colour_count, palette.len());
+
+    for (colour, &(red, green, blue)) in colours.iter().zip(&palette) {
+      assert_eq!(*colour, ParaColour { red, green, blue });
+    }
+
+    for (pixel, &index) in rgba.chunks_exact(4).zip(&indexed) {
+      let (red, green, blue) = palette[usize::from(index)];
+
+      assert_eq!(pixel, [red, green, blue, 255]);
+    }
+  }
+
+  unsafe { para_free(flipnote) };
+}
+
+#[test]
+fn sound_tracks() {
+  let flipnote = open("samplememo_02");
+  let expected =
+    flipnote::open(memo_path("samplememo_02").to_str().unwrap()).unwrap();
+  let (mut count, mut sample_rate, mut length) = (0, 0, 0);
+
+  unsafe {
+    assert_eq!(
+      para_sound_track_count(flipnote, &raw mut count),
+      ParaStatus::Ok
+    );
+    assert_eq!(
+      para_sample_rate(flipnote, &raw mut sample_rate),
+      ParaStatus::Ok
+    );
+  }
+
+  assert_eq!(count, expected.sound_tracks().len());
+  assert_eq!(sample_rate, 8192);
+
+  for track in 0..count {
+    let mut name = [0; 8];
+
+    unsafe {
+      assert_eq!(
+        para_sound_track_name(
+          flipnote,
+          track,
+          name.as_mut_ptr(),
+          name.len(),
+          ptr::null_mut(),
+        ),
+        ParaStatus::Ok
+      );
+      assert_eq!(
+        para_sound_track(flipnote, track, ptr::null_mut(), 0, &raw mut length),
+        ParaStatus::Ok
+      );
+    }
+
+    let mut samples = vec![0; length];
+
+    unsafe {
+      assert_eq!(
+        para_sound_track(
+          flipnote,
+          track,
+          samples.as_mut_ptr(),
+          samples.len(),
+          ptr::null_mut(),
+        ),
+        ParaStatus::Ok
+      );
+    }
+
+    assert_eq!(
+      unsafe { CStr::from_ptr(name.as_ptr()) }.to_str().unwrap(),
+      expected.sound_tracks()[track].name
+    );
+    assert_eq!(samples, expected.decode_sound_track(track));
+  }
+
+  unsafe { para_free(flipnote) };
+}
+
+#[test]
+fn errors() {
+  let flipnote = open("mrjohn");
+  let mut opened = ptr::null_mut();
+  let mut buffer = vec![0; 16];
+  let (mut length, mut frame_count) = (0, 0);
+  let missing = CString::new("missing.ppm").unwrap();
+  let palette = CString::new("not a palette").unwrap();
+
+  unsafe {
+    assert_eq!(
+      para_open_buffer(c"KIC".as_ptr().cast(), 4, &raw mut opened),
+      ParaStatus::UnsupportedFormat
+    );
+    assert_eq!(
+      para_open_path(missing.as_ptr(), &raw mut opened),
+      ParaStatus::Io
+    );
+    assert_eq!(
+      para_open_path(ptr::null(), &raw mut opened),
+      ParaStatus::NullPointer
+    );
+    assert!(opened.is_null());
+    assert_eq!(
+      para_frame_indexed(flipnote, 0, buffer.as_mut_ptr(), buffer.len()),
+      ParaStatus::BufferTooSmall
+    );
+    assert_eq!(
+      para_frame_rgba(flipnote, 9999, false, buffer.as_mut_ptr(), 0),
+      ParaStatus::OutOfRange
+    );
+    assert_eq!(
+      para_sound_track(flipnote, 99, ptr::null_mut(), 0, &raw mut length),
+      ParaStatus::OutOfRange
+    );
+    assert_eq!(
+      para_metadata_text(
+        flipnote,
+        ParaText::RootAuthorName,
+        buffer.as_mut_ptr().cast(),
+        1,
+        &raw mut length,
+      ),
+      ParaStatus::BufferTooSmall
+    );
+    assert!(length > 1);
+    assert_eq!(
+      para_set_palette(flipnote, palette.as_ptr()),
+      ParaStatus::InvalidPalette
+    );
+    assert_eq!(
+      para_frame_count(ptr::null(), &raw mut frame_count),
+      ParaStatus::NullPointer
+    );
+    assert_eq!(
+      CStr::from_ptr(para_status_message(ParaStatus::BufferTooSmall)),
+      c"the buffer is too small"
+    );
+
+    para_free(flipnote);
+    para_free(ptr::null_mut());
+  }
+}
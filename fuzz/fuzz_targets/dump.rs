@@ -0,0 +1,20 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+#![no_main]
+
+use {
+  libfuzzer_sys::fuzz_target,
+  para_cli::{flipnote::Flipnote, ppm::PPMParser},
+};
+
+fuzz_target!(|data: &[u8]| {
+  let mut parser = PPMParser::new(data.to_vec());
+
+  parser.load();
+  parser.dump_json(true);
+  parser.info_json();
+  parser.summary();
+  parser.get_thumbnail();
+  parser.get_signature().is_present();
+});
@@ -0,0 +1,19 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+#![no_main]
+
+use {
+  libfuzzer_sys::fuzz_target,
+  para_cli::{flipnote::Flipnote, palette::Palette, ppm::PPMParser},
+};
+
+fuzz_target!(|data: &[u8]| {
+  let mut parser = PPMParser::new(data.to_vec());
+
+  parser.load();
+
+  for index in 0..=usize::from(parser.get_frame_count()) {
+    parser.get_frame_palette(index, &Palette::DSI);
+  }
+});
@@ -0,0 +1,30 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+#![no_main]
+
+use {
+  libfuzzer_sys::fuzz_target,
+  para_cli::{flipnote::Flipnote, ppm::PPMParser},
+};
+
+/// Frames decoded in playback order per input; diff frames are based on the
+/// frame before them, so seeking further would decode every frame between
+const FRAMES: usize = 4;
+
+fuzz_target!(|data: &[u8]| {
+  let mut parser = PPMParser::new(data.to_vec());
+
+  parser.load();
+
+  let frame_count = usize::from(parser.get_frame_count());
+
+  for index in 0..FRAMES.min(frame_count) {
+    parser.get_frame_pixels(index);
+    parser.get_layer_pixels(index);
+  }
+
+  // Seek back to the first frame, and past the last
+  parser.get_frame_pixels(0);
+  parser.get_frame_pixels(frame_count);
+});
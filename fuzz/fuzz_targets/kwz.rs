@@ -0,0 +1,38 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+#![no_main]
+
+use {
+  libfuzzer_sys::fuzz_target,
+  para_cli::{flipnote::Flipnote, kwz::KWZParser, palette::Palette},
+};
+
+/// Frames decoded in playback order per input, as in `frame_pixels`
+const FRAMES: usize = 4;
+
+fuzz_target!(|data: &[u8]| {
+  let mut parser = KWZParser::new(data.to_vec());
+
+  if parser.load().is_err() {
+    return;
+  }
+
+  let frame_count = usize::from(parser.get_frame_count());
+
+  for index in 0..FRAMES.min(frame_count) {
+    parser.get_frame_pixels(index);
+    parser.get_layer_pixels(index);
+    parser.get_frame_palette(index, &Palette::N3DS);
+  }
+
+  // Seek back to the first frame, and past the last
+  parser.get_frame_pixels(0);
+  parser.get_frame_pixels(frame_count);
+  parser.dump_json(true);
+  parser.info_json();
+
+  for index in 0..parser.sound_tracks().len() {
+    parser.decode_sound_track(index);
+  }
+});
@@ -0,0 +1,14 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+#![no_main]
+
+use {
+  libfuzzer_sys::fuzz_target,
+  para_cli::ppm::{PPMParser, Recovery},
+};
+
+fuzz_target!(|data: &[u8]| {
+  PPMParser::new(data.to_vec()).load();
+  PPMParser::new(data.to_vec()).load_recovering(Recovery::Repeat);
+});
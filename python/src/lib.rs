@@ -0,0 +1,235 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Python bindings to the decoder behind `para`, for working with Flipnotes
+//! as `numpy` arrays
+//!
+//! ```python
+//! import para
+//!
+//! flipnote = para.open("samplememo_02.ppm")
+//! frames = flipnote.frames()  # uint8, (frame_count, height, width)
+//! colours = flipnote.palette(0)  # [(r, g, b), ...], indexed by pixel
+//! bgm = flipnote.sound_track("bgm")  # int16 at flipnote.sample_rate
+//! ```
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+#![allow(clippy::missing_errors_doc)]
+
+use {
+  numpy::{IntoPyArray, PyArray1, PyArray2, PyArray3},
+  para_cli::{flipnote, palette::Palette},
+  pyo3::{
+    exceptions::{PyIndexError, PyKeyError, PyValueError},
+    prelude::*,
+    types::{PyBool, PyDict, PyFloat, PyList, PyString},
+  },
+};
+
+/// A `.ppm` or `.kwz`, detected by its contents
+///
+/// Frames are palette indices; zero is the paper, and the remaining values
+/// index into `palette(index)`.
+#[pyclass(unsendable, module = "para")]
+struct Flipnote {
+  flipnote: Box<dyn flipnote::Flipnote>,
+  palette:  Palette,
+}
+impl Flipnote {
+  fn check_frame(&self, index: usize) -> PyResult<()> {
+    if index < usize::from(self.flipnote.get_frame_count()) {
+      Ok(())
+    } else {
+      Err(PyIndexError::new_err(format!("no frame {index}")))
+    }
+  }
+}
+#[pymethods]
+impl Flipnote {
+  #[new]
+  fn new(data: &[u8]) -> PyResult<Self> {
+    let flipnote = flipnote::from_bytes(data.to_vec()).ok_or_else(|| {
+      PyValueError::new_err("unsupported format, expected a Flipnote")
+    })?;
+    let palette = flipnote.default_palette();
+
+    Ok(Self { flipnote, palette })
+  }
+
+  /// Frame width in pixels
+  #[getter]
+  fn width(&self) -> usize { self.flipnote.dimensions().0 }
+
+  /// Frame height in pixels
+  #[getter]
+  fn height(&self) -> usize { self.flipnote.dimensions().1 }
+
+  #[getter]
+  fn frame_count(&self) -> u16 { self.flipnote.get_frame_count() }
+
+  #[getter]
+  fn thumb_index(&self) -> u16 { self.flipnote.get_thumb_index() }
+
+  /// Frames per second
+  #[getter]
+  fn framerate(&self) -> f64 { self.flipnote.get_framerate() }
+
+  /// Playback length in seconds
+  #[getter]
+  fn duration(&self) -> f64 { self.flipnote.get_duration() }
+
+  /// Samples per second of the sound tracks
+  #[getter]
+  fn sample_rate(&self) -> u32 { self.flipnote.sample_rate() }
+
+  fn __len__(&self) -> usize { usize::from(self.flipnote.get_frame_count()) }
+
+  /// Every metadata field, as shown by `para info --json`
+  fn metadata<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+    to_python(py, &self.flipnote.info_json())
+  }
+
+  /// Draw frames with a preset, such as `"3ds"`, or with colours given as
+  /// `"black,white,red,blue"` in hexadecimal
+  fn set_palette(&mut self, palette: &str) -> PyResult<()> {
+    self.palette = Palette::parse(palette).map_err(PyValueError::new_err)?;
+
+    Ok(())
+  }
+
+  /// The colours of a frame as `(red, green, blue)`, indexed by its pixels
+  fn palette(&mut self, index: usize) -> PyResult<Vec<(u8, u8, u8)>> {
+    self.check_frame(index)?;
+
+    Ok(self.flipnote.get_frame_palette(index, &self.palette))
+  }
+
+  /// A frame as a `(height, width)` array of palette indices
+  fn frame<'py>(
+    &mut self,
+    py: Python<'py>,
+    index: usize,
+  ) -> PyResult<Bound<'py, PyArray2<u8>>> {
+    self.check_frame(index)?;
+
+    Ok(PyArray2::from_vec2(py, &self.flipnote.get_frame_pixels(index))?)
+  }
+
+  /// Each layer of a frame on its own canvas, as a `(layer_count, height,
+  /// width)` array of palette indices
+  fn layers<'py>(
+    &mut self,
+    py: Python<'py>,
+    index: usize,
+  ) -> PyResult<Bound<'py, PyArray3<u8>>> {
+    self.check_frame(index)?;
+
+    Ok(PyArray3::from_vec3(py, &self.flipnote.get_layer_pixels(index))?)
+  }
+
+  /// Every frame in playback order, as a `(frame_count, height, width)`
+  /// array of palette indices
+  fn frames<'py>(
+    &mut self,
+    py: Python<'py>,
+  ) -> PyResult<Bound<'py, PyArray3<u8>>> {
+    let frames = (0..usize::from(self.flipnote.get_frame_count()))
+      .map(|index| self.flipnote.get_frame_pixels(index))
+      .collect::<Vec<_>>();
+
+    Ok(PyArray3::from_vec3(py, &frames)?)
+  }
+
+  /// Names of the sound tracks, in the order they're stored
+  fn sound_tracks(&self) -> Vec<&'static str> {
+    self.flipnote.sound_tracks().into_iter().map(|track| track.name).collect()
+  }
+
+  /// A sound track, such as `"bgm"` or `"se1"`, as 16-bit PCM at
+  /// `sample_rate`
+  fn sound_track<'py>(
+    &self,
+    py: Python<'py>,
+    name: &str,
+  ) -> PyResult<Bound<'py, PyArray1<i16>>> {
+    let index = self
+      .flipnote
+      .sound_tracks()
+      .iter()
+      .position(|track| track.name == name)
+      .ok_or_else(|| PyKeyError::new_err(format!("no sound track {name}")))?;
+
+    Ok(self.flipnote.decode_sound_track(index).into_pyarray(py))
+  }
+
+  fn __repr__(&self) -> String {
+    let metadata = self.flipnote.metadata();
+
+    format!(
+      "<Flipnote {} by {}, {} frames>",
+      metadata.current_filename,
+      metadata.authors[2].0,
+      self.flipnote.get_frame_count()
+    )
+  }
+}
+
+/// Convert `para info --json` output to dictionaries, lists, and scalars
+fn to_python<'py>(
+  py: Python<'py>,
+  value: &serde_json::Value,
+) -> PyResult<Bound<'py, PyAny>> {
+  Ok(match value {
+    serde_json::Value::Null => py.None().into_bound(py),
+    serde_json::Value::Bool(value) =>
+      PyBool::new(py, *value).to_owned().into_any(),
+    serde_json::Value::Number(number) =>
+      if let Some(integer) = number.as_i64() {
+        integer.into_pyobject(py)?.into_any()
+      } else if let Some(integer) = number.as_u64() {
+        integer.into_pyobject(py)?.into_any()
+      } else {
+        PyFloat::new(py, number.as_f64().unwrap_or_default()).into_any()
+      },
+    serde_json::Value::String(string) => PyString::new(py, string).into_any(),
+    serde_json::Value::Array(values) => PyList::new(
+      py,
+      values
+        .iter()
+        .map(|value| to_python(py, value))
+        .collect::<PyResult<Vec<_>>>()?,
+    )?
+    .into_any(),
+    serde_json::Value::Object(fields) => {
+      let dictionary = PyDict::new(py);
+
+      for (key, value) in fields {
+        dictionary.set_item(key, to_python(py, value)?)?;
+      }
+
+      dictionary.into_any()
+    }
+  })
+}
+
+/// Load a Flipnote of any supported format from a file
+#[pyfunction]
+fn open(path: std::path::PathBuf) -> PyResult<Flipnote> {
+  Flipnote::new(&std::fs::read(path)?)
+}
+
+#[pymodule]
+fn para(module: &Bound<'_, PyModule>) -> PyResult<()> {
+  module.add_class::<Flipnote>()?;
+  module.add_function(wrap_pyfunction!(open, module)?)?;
+
+  Ok(())
+}
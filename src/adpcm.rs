@@ -0,0 +1,109 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! IMA ADPCM, which Flipnote Studio stores its sound tracks as, and the
+//! variable-width variant Flipnote Studio 3D stores its own as
+//!
+//! <https://github.com/pbsds/hatena-server/wiki/PPM-format#sound-data>
+//! <https://github.com/Flipnote-Collective/flipnote-studio-3d-docs/wiki/kwz-format#ksn-sound-data>
+
+/// Difference between neighbouring samples at each step index
+const STEP_TABLE: [i32; 89] = [
+  7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45,
+  50, 55, 60, 66, 73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230,
+  253, 279, 307, 337, 371, 408, 449, 494, 544, 598, 658, 724, 796, 876, 963,
+  1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499, 2749, 3024, 3327,
+  3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493, 10442,
+  11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794,
+  32767,
+];
+
+/// How each 4-bit sample moves the step index
+const INDEX_TABLE: [i32; 16] =
+  [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
+
+/// How each 2-bit sample moves the step index
+const INDEX_TABLE_2_BIT: [i32; 4] = [-1, 2, -1, 2];
+
+/// Difference a 4-bit sample makes to the predictor
+const fn difference(step: i32, sample: u8) -> i32 {
+  let mut difference = step >> 3;
+
+  if sample & 0x1 != 0 {
+    difference += step >> 2;
+  }
+
+  if sample & 0x2 != 0 {
+    difference += step >> 1;
+  }
+
+  if sample & 0x4 != 0 {
+    difference += step;
+  }
+
+  if sample & 0x8 != 0 { -difference } else { difference }
+}
+
+/// Decode 4-bit samples, stored low nibble first, to 16-bit PCM
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn decode(bytes: &[u8]) -> Vec<i16> {
+  let mut predictor = 0i32;
+  let mut step_index = 0i32;
+
+  bytes
+    .iter()
+    .flat_map(|&byte| [byte & 0x0F, byte >> 4])
+    .map(|sample| {
+      predictor = (predictor
+        + difference(STEP_TABLE[step_index as usize], sample))
+      .clamp(i32::from(i16::MIN), i32::from(i16::MAX));
+      step_index = (step_index + INDEX_TABLE[usize::from(sample)]).clamp(0, 88);
+
+      predictor as i16
+    })
+    .collect()
+}
+
+/// Decode Flipnote Studio 3D's samples to 16-bit PCM
+///
+/// Each byte is read from its lowest bits up, as 4-bit samples while the
+/// step index is at least 18, and as 2-bit samples otherwise; the last two
+/// bits of a byte are always a 2-bit sample. The predictor is kept to 12 bits
+/// and scaled up, and the step index starts at 40 and is kept to 79.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn decode_kwz(bytes: &[u8]) -> Vec<i16> {
+  let mut predictor = 0i32;
+  let mut step_index = 40i32;
+  let mut samples = Vec::with_capacity(bytes.len() * 2);
+
+  for &byte in bytes {
+    let mut bit = 0;
+
+    while bit < 8 {
+      let step = STEP_TABLE[step_index as usize];
+      let sample = byte >> bit;
+
+      if step_index < 18 || bit > 4 {
+        let sample = sample & 0x3;
+        let difference = (step >> 3) + if sample & 0x1 != 0 { step } else { 0 };
+
+        predictor += if sample & 0x2 != 0 { -difference } else { difference };
+        step_index += INDEX_TABLE_2_BIT[usize::from(sample)];
+        bit += 2;
+      } else {
+        let sample = sample & 0xF;
+
+        predictor += difference(step, sample);
+        step_index += INDEX_TABLE[usize::from(sample)];
+        bit += 4;
+      }
+
+      predictor = predictor.clamp(-2048, 2047);
+      step_index = step_index.clamp(0, 79);
+
+      samples.push((predictor * 16) as i16);
+    }
+  }
+
+  samples
+}
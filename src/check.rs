@@ -0,0 +1,348 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use {
+  crate::{
+    ppm::{TMB_SIZE, framerates, sound_header_offset},
+    signature::{SIGNATURE_SIZE, Signature},
+  },
+  std::collections::{BTreeMap, BTreeSet},
+};
+
+/// Where the animation section, and so the offset table, starts
+const ANIMATION_OFFSET: usize = 0x06A0;
+
+/// A structural problem with a PPM, and the offset of the bytes at fault
+pub struct Issue {
+  pub offset:  usize,
+  pub message: String,
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+  Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+  Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// Structural checks of a PPM, read straight from its bytes so that
+/// damaged files are reported rather than decoded
+#[derive(Default)]
+struct Checker<'a> {
+  bytes:   &'a [u8],
+  issues:  Vec<Issue>,
+  /// Frames which can't be decoded, by index, and why
+  damaged: BTreeMap<usize, String>,
+}
+impl Checker<'_> {
+  fn report(&mut self, offset: usize, message: String) {
+    self.issues.push(Issue { offset, message });
+  }
+
+  fn report_frame(&mut self, index: usize, offset: usize, message: String) {
+    self.damaged.insert(index, message.clone());
+    self.report(offset, message);
+  }
+
+  /// Check the header, returning the frame count and the animation and
+  /// sound data sizes if they could be read
+  fn check_header(&mut self) -> Option<(usize, usize, usize)> {
+    if self.bytes.get(..4) != Some(b"PARA") {
+      self.report(0, "expected the PARA magic of a .ppm".to_string());
+
+      return None;
+    }
+
+    let (Some(animation_size), Some(sound_size), Some(frame_count)) = (
+      read_u32(self.bytes, 0x04),
+      read_u32(self.bytes, 0x08),
+      read_u16(self.bytes, 0x0C),
+    ) else {
+      self.report(0, "header is truncated".to_string());
+
+      return None;
+    };
+    let frame_count = usize::from(frame_count) + 1;
+
+    if self.bytes.len() < ANIMATION_OFFSET + 8 {
+      self.report(
+        self.bytes.len(),
+        format!(
+          "file ends before the animation section at 0x{ANIMATION_OFFSET:X}{}",
+          if self.bytes.len() as u64 <= TMB_SIZE {
+            ", as .tmb files do"
+          } else {
+            ""
+          }
+        ),
+      );
+
+      return None;
+    }
+
+    if ANIMATION_OFFSET + animation_size as usize > self.bytes.len() {
+      self.report(
+        0x04,
+        format!(
+          "animation section ({animation_size} bytes) runs past the end of \
+           the file"
+        ),
+      );
+    }
+
+    if let Some(thumb_index) = read_u16(self.bytes, 0x12)
+      && usize::from(thumb_index) >= frame_count
+    {
+      self.report(
+        0x12,
+        format!(
+          "thumb_index {thumb_index} isn't less than frame_count {frame_count}"
+        ),
+      );
+    }
+
+    Some((frame_count, animation_size as usize, sound_size as usize))
+  }
+
+  /// Check that the offset table has an entry for each frame, and that each
+  /// points inside the animation section, returning the absolute offsets of
+  /// the frames which do
+  fn check_offset_table(
+    &mut self,
+    frame_count: usize,
+    animation_end: usize,
+  ) -> Vec<(usize, usize)> {
+    let table_size =
+      read_u16(self.bytes, ANIMATION_OFFSET).map_or(0, usize::from);
+    let data_offset = ANIMATION_OFFSET + 8 + table_size;
+
+    if table_size % 4 != 0 || table_size / 4 != frame_count {
+      self.report(
+        ANIMATION_OFFSET,
+        format!(
+          "offset table is {table_size} bytes, but {frame_count} frames need \
+           {}",
+          frame_count * 4
+        ),
+      );
+    }
+
+    (0..table_size / 4)
+      .filter_map(|index| {
+        let entry = ANIMATION_OFFSET + 8 + index * 4;
+        let Some(offset) = read_u32(self.bytes, entry) else {
+          self.report_frame(
+            index,
+            entry,
+            format!("frame {index}'s offset is truncated"),
+          );
+
+          return None;
+        };
+        let offset = data_offset + offset as usize;
+
+        if offset >= animation_end {
+          self.report_frame(
+            index,
+            entry,
+            format!(
+              "frame {index}'s offset (0x{offset:X}) points outside the \
+               animation section"
+            ),
+          );
+
+          return None;
+        }
+
+        Some((index, offset))
+      })
+      .collect()
+  }
+
+  /// Walk each frame's line types and the line data they describe, checking
+  /// that it ends before the next frame's data does
+  fn check_frames(&mut self, frames: &[(usize, usize)], animation_end: usize) {
+    let mut starts =
+      frames.iter().map(|&(_, offset)| offset).collect::<Vec<_>>();
+
+    starts.sort_unstable();
+    starts.dedup();
+
+    for &(index, offset) in frames {
+      let limit = starts
+        .get(starts.partition_point(|&start| start <= offset))
+        .copied()
+        .unwrap_or(animation_end)
+        .min(self.bytes.len());
+      let end = self.walk_frame(offset);
+
+      if offset >= self.bytes.len() {
+        self.report_frame(
+          index,
+          offset,
+          format!("frame {index}'s data starts past the end of the file"),
+        );
+      } else if end.is_none_or(|end| end > limit) {
+        self.report_frame(
+          index,
+          offset,
+          format!(
+            "frame {index}'s line data overruns its frame, which ends at \
+             0x{limit:X}"
+          ),
+        );
+      }
+    }
+  }
+
+  /// The offset at which a frame's data ends, if it ends within the file
+  fn walk_frame(&self, offset: usize) -> Option<usize> {
+    let header = *self.bytes.get(offset)?;
+    let mut position = offset + 1;
+
+    if (header >> 5) & 0x03 != 0 {
+      position += 2;
+    }
+
+    let line_types = self.bytes.get(position..position + 96)?;
+
+    position += 96;
+
+    for layer in line_types.chunks_exact(48) {
+      for line in 0..192 {
+        position += match layer[line / 4] >> ((line % 4) * 2) & 0x03 {
+          1 | 2 => {
+            let chunk_usage = u32::from_be_bytes(
+              self.bytes.get(position..position + 4)?.try_into().ok()?,
+            );
+
+            4 + chunk_usage.count_ones() as usize
+          }
+          3 => 32,
+          _ => 0,
+        };
+      }
+    }
+
+    (position <= self.bytes.len()).then_some(position)
+  }
+
+  /// Check the speeds and track sizes of the sound header, and that the
+  /// file holds its sound data and a signature
+  fn check_sound(
+    &mut self,
+    frame_count: usize,
+    animation_size: usize,
+    sound_size: usize,
+  ) {
+    #[allow(clippy::cast_possible_truncation)]
+    let header =
+      sound_header_offset(animation_size as u64, frame_count as u64) as usize;
+
+    let Some(track_sizes) = (0..4)
+      .map(|track| read_u32(self.bytes, header + track * 4))
+      .collect::<Option<Vec<_>>>()
+    else {
+      self.report(header, "sound header is truncated".to_string());
+
+      return;
+    };
+    let track_total =
+      track_sizes.iter().map(|&size| size as usize).sum::<usize>();
+
+    for (name, offset) in [("frame_speed", 16), ("bgm_speed", 17)] {
+      let speed =
+        self.bytes.get(header + offset).map(|&raw| 8 - i16::from(raw));
+
+      if !speed
+        .and_then(|speed| u8::try_from(speed).ok())
+        .is_some_and(|speed| framerates().contains_key(&speed))
+      {
+        self.report(
+          header + offset,
+          format!(
+            "{name} {} doesn't map to a framerate",
+            speed.map_or_else(
+              || "(missing)".to_string(),
+              |speed| speed.to_string()
+            )
+          ),
+        );
+      }
+    }
+
+    // Real files often leave the size at zero, so only a size which
+    // disagrees with the tracks is a problem
+    if sound_size != 0 && sound_size != track_total {
+      self.report(
+        0x08,
+        format!(
+          "sound_data_size is {sound_size}, but the sound tracks total \
+           {track_total} bytes"
+        ),
+      );
+    }
+
+    let signature_offset = header + 0x20 + track_total;
+
+    if signature_offset > self.bytes.len() {
+      self.report(
+        header,
+        format!(
+          "sound data ({track_total} bytes) runs past the end of the file"
+        ),
+      );
+    } else if !Signature::new(self.bytes, signature_offset).is_present() {
+      self.report(
+        signature_offset,
+        format!("no signature ({SIGNATURE_SIZE} bytes) follows the sound data"),
+      );
+    }
+  }
+}
+
+/// Run every structural check on a PPM without decoding its frames
+pub fn check(bytes: &[u8]) -> Vec<Issue> {
+  let mut checker = Checker { bytes, ..Checker::default() };
+
+  if let Some((frame_count, animation_size, sound_size)) =
+    checker.check_header()
+  {
+    let animation_end = ANIMATION_OFFSET + animation_size;
+    let frames = checker.check_offset_table(frame_count, animation_end);
+
+    checker.check_frames(&frames, animation_end);
+    checker.check_sound(frame_count, animation_size, sound_size);
+  }
+
+  checker.issues.sort_by_key(|issue| issue.offset);
+
+  checker.issues
+}
+
+/// The frames of a PPM which can't be decoded, by index, and why; those
+/// whose offsets are missing, point outside the animation section, or whose
+/// line data overruns the frame or the file
+pub fn damaged_frames(bytes: &[u8]) -> BTreeMap<usize, String> {
+  let mut checker = Checker { bytes, ..Checker::default() };
+
+  if let Some((frame_count, animation_size, _)) = checker.check_header() {
+    let animation_end = ANIMATION_OFFSET + animation_size;
+    let frames = checker.check_offset_table(frame_count, animation_end);
+
+    checker.check_frames(&frames, animation_end);
+
+    let located =
+      frames.iter().map(|&(index, _)| index).collect::<BTreeSet<_>>();
+
+    for index in (0..frame_count).filter(|index| !located.contains(index)) {
+      checker
+        .damaged
+        .entry(index)
+        .or_insert_with(|| format!("frame {index} has no offset"));
+    }
+  }
+
+  checker.damaged
+}
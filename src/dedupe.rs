@@ -0,0 +1,182 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use {
+  crate::{
+    flipnote::{Flipnote, open, walk},
+    palette::Palette,
+  },
+  sha1::{Digest, Sha1},
+  std::path::Path,
+};
+
+/// Width and height of the grid frames are reduced to before hashing; one
+/// column wider than the hash so that each bit compares two neighbours
+const GRID_WIDTH: usize = 9;
+const GRID_HEIGHT: usize = 8;
+
+/// How much ink covers each cell of a frame reduced to a
+/// `GRID_WIDTH`x`GRID_HEIGHT` grid
+#[allow(clippy::cast_precision_loss)]
+fn ink_grid(pixels: &[Vec<u8>]) -> [f64; GRID_WIDTH * GRID_HEIGHT] {
+  let mut grid = [0.0; GRID_WIDTH * GRID_HEIGHT];
+  let height = pixels.len().max(1);
+
+  for (y, row) in pixels.iter().enumerate() {
+    let width = row.len().max(1);
+
+    for (x, &pixel) in row.iter().enumerate() {
+      if pixel != 0 {
+        grid
+          [(y * GRID_HEIGHT / height) * GRID_WIDTH + x * GRID_WIDTH / width] +=
+          1.0;
+      }
+    }
+  }
+
+  grid
+}
+
+/// Difference hash of a grid; each bit is set when a cell holds more ink
+/// than its right neighbour
+fn difference_hash(grid: &[f64; GRID_WIDTH * GRID_HEIGHT]) -> u64 {
+  grid
+    .chunks_exact(GRID_WIDTH)
+    .flat_map(|row| row.windows(2).map(|pair| pair[0] > pair[1]))
+    .fold(0, |hash, bit| (hash << 1) | u64::from(bit))
+}
+
+/// The fingerprints of a Flipnote's decoded frames, which don't depend on
+/// its metadata
+pub struct Fingerprint {
+  pub path:      String,
+  pub metadata:  serde_json::Value,
+  /// SHA-1 of every frame's pixels and colours
+  pub exact:     [u8; 20],
+  /// Perceptual hash of each frame
+  pub frames:    Vec<u64>,
+  /// Perceptual hash of the ink of every frame combined
+  pub animation: u64,
+}
+impl Fingerprint {
+  pub fn new(path: &str, flipnote: &mut dyn Flipnote) -> Self {
+    let mut exact = Sha1::new();
+    let mut animation = [0.0; GRID_WIDTH * GRID_HEIGHT];
+    let frames = (0..usize::from(flipnote.get_frame_count()))
+      .map(|index| {
+        let pixels = flipnote.get_frame_pixels(index);
+        let grid = ink_grid(&pixels);
+
+        pixels.iter().for_each(|row| exact.update(row));
+
+        // The same palette indices can be drawn in different pen colours
+        for colour in flipnote.get_frame_palette(index, &Palette::default()) {
+          exact.update(<[u8; 3]>::from(colour));
+        }
+
+        for (total, cell) in animation.iter_mut().zip(grid) {
+          *total += cell;
+        }
+
+        difference_hash(&grid)
+      })
+      .collect();
+
+    Self {
+      path: path.to_string(),
+      metadata: flipnote.metadata_json(),
+      exact: exact.finalize().into(),
+      frames,
+      animation: difference_hash(&animation),
+    }
+  }
+
+  /// Mean number of differing bits between the frames of two Flipnotes, in
+  /// playback order; frames only one of them has count as entirely different
+  #[allow(clippy::cast_precision_loss)]
+  pub fn distance(&self, other: &Self) -> f64 {
+    let frame_count = self.frames.len().max(other.frames.len());
+
+    if frame_count == 0 {
+      return 0.0;
+    }
+
+    let unmatched = self.frames.len().abs_diff(other.frames.len()) * 64;
+    let matched = self
+      .frames
+      .iter()
+      .zip(&other.frames)
+      .map(|(a, b)| (a ^ b).count_ones() as usize)
+      .sum::<usize>();
+
+    (matched + unmatched) as f64 / frame_count as f64
+  }
+
+  /// Metadata fields whose values differ from another Flipnote's
+  pub fn metadata_differences(&self, other: &Self) -> Vec<String> {
+    self
+      .metadata
+      .as_object()
+      .into_iter()
+      .flatten()
+      .filter(|(key, value)| other.metadata.get(key.as_str()) != Some(value))
+      .map(|(key, _)| key.clone())
+      .collect()
+  }
+}
+
+/// Flipnotes which are the same animation as the first, by index; each
+/// either with identical frames, or within the distance threshold of it
+pub struct Group {
+  pub members: Vec<usize>,
+}
+
+/// Fingerprint every Flipnote in a directory and its subdirectories,
+/// skipping files which aren't Flipnotes, such as `.tmb` files
+pub fn fingerprint_directory(directory: &Path) -> Vec<Fingerprint> {
+  walk(directory)
+    .iter()
+    .filter_map(|path| {
+      let path = path.to_string_lossy();
+      let mut flipnote = open(&path).ok()?;
+
+      Some(Fingerprint::new(&path, flipnote.as_mut()))
+    })
+    .collect()
+}
+
+/// Group Flipnotes whose frames are identical, or whose animations and
+/// frames are within `threshold` differing bits of each other
+pub fn group(fingerprints: &[Fingerprint], threshold: f64) -> Vec<Group> {
+  let mut grouped = vec![false; fingerprints.len()];
+  let mut groups = Vec::new();
+
+  for first in 0..fingerprints.len() {
+    if grouped[first] {
+      continue;
+    }
+
+    let fingerprint = &fingerprints[first];
+    let members = (first..fingerprints.len())
+      .filter(|&other| {
+        let other = &fingerprints[other];
+
+        fingerprint.exact == other.exact
+          || (f64::from((fingerprint.animation ^ other.animation).count_ones())
+            <= threshold
+            && fingerprint.distance(other) <= threshold)
+      })
+      .filter(|&other| !grouped[other])
+      .collect::<Vec<_>>();
+
+    if members.len() > 1 {
+      for &member in &members {
+        grouped[member] = true;
+      }
+
+      groups.push(Group { members });
+    }
+  }
+
+  groups
+}
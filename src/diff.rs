@@ -0,0 +1,176 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use crate::{export::IndexedFrame, flipnote::Flipnote};
+
+/// Colours of a highlighted frame; the paper, ink both frames share, ink
+/// only the first has, ink only the second has, and ink or paper whose colour
+/// changed
+const HIGHLIGHT_COLOURS: [(u8, u8, u8); 5] = [
+  (0xFF, 0xFF, 0xFF),
+  (0xC8, 0xC8, 0xC8),
+  (0xE0, 0x20, 0x20),
+  (0x20, 0xA0, 0x20),
+  (0x20, 0x40, 0xE0),
+];
+
+/// A frame which the two Flipnotes both have, but whose pixels or colours
+/// differ
+pub struct FrameDiff {
+  pub index:          usize,
+  pub changed_pixels: usize,
+  /// Both frames drawn over each other, with the changed ink highlighted
+  pub highlight:      Option<IndexedFrame>,
+}
+
+/// What changed between two Flipnotes, such as one and the Flipnote it was
+/// edited from; frames are compared in playback order
+pub struct Diff {
+  /// Metadata fields which differ, as `(field, first value, second value)`
+  pub metadata:     Vec<(String, serde_json::Value, serde_json::Value)>,
+  pub frame_counts: (usize, usize),
+  /// Whether both Flipnotes have the same dimensions, without which their
+  /// frames can't be compared
+  pub comparable:   bool,
+  pub frames:       Vec<FrameDiff>,
+}
+impl Diff {
+  /// Compare two Flipnotes, keeping a highlighted image of each changed frame
+  /// when `highlight` is set
+  ///
+  /// Pixels are compared by the colours they're drawn with in each
+  /// Flipnote's default palette as well as by palette index, so a frame whose
+  /// paper or pens were recoloured has changed.
+  pub fn new(
+    first: &mut dyn Flipnote,
+    second: &mut dyn Flipnote,
+    highlight: bool,
+  ) -> Self {
+    let first_metadata = first.metadata_json();
+    let second_metadata = second.metadata_json();
+    let mut fields = first_metadata
+      .as_object()
+      .into_iter()
+      .chain(second_metadata.as_object())
+      .flat_map(|object| object.keys().cloned())
+      .collect::<Vec<_>>();
+
+    fields.sort();
+    fields.dedup();
+
+    let frame_counts = (
+      usize::from(first.get_frame_count()),
+      usize::from(second.get_frame_count()),
+    );
+    let comparable = first.dimensions() == second.dimensions();
+    let palettes = (first.default_palette(), second.default_palette());
+    let frames = if comparable {
+      (0..frame_counts.0.min(frame_counts.1))
+        .filter_map(|index| {
+          compare_frames(
+            index,
+            (
+              &first.get_frame_pixels(index),
+              &first.get_frame_palette(index, &palettes.0),
+            ),
+            (
+              &second.get_frame_pixels(index),
+              &second.get_frame_palette(index, &palettes.1),
+            ),
+            highlight,
+          )
+        })
+        .collect()
+    } else {
+      Vec::new()
+    };
+
+    Self {
+      metadata: fields
+        .into_iter()
+        .filter(|field| first_metadata.get(field) != second_metadata.get(field))
+        .map(|field| {
+          let values = (
+            first_metadata.get(&field).cloned().unwrap_or_default(),
+            second_metadata.get(&field).cloned().unwrap_or_default(),
+          );
+
+          (field, values.0, values.1)
+        })
+        .collect(),
+      frame_counts,
+      comparable,
+      frames,
+    }
+  }
+
+  pub fn to_json(&self) -> serde_json::Value {
+    serde_json::json!({
+      "metadata": self
+        .metadata
+        .iter()
+        .map(|(field, first, second)| (
+          field.clone(),
+          serde_json::json!([first, second]),
+        ))
+        .collect::<serde_json::Map<_, _>>(),
+      "frame_counts": [self.frame_counts.0, self.frame_counts.1],
+      "comparable": self.comparable,
+      "removed_frames":
+        (self.frame_counts.1..self.frame_counts.0).collect::<Vec<_>>(),
+      "added_frames":
+        (self.frame_counts.0..self.frame_counts.1).collect::<Vec<_>>(),
+      "changed_frames": self
+        .frames
+        .iter()
+        .map(|frame| serde_json::json!({
+          "index": frame.index,
+          "changed_pixels": frame.changed_pixels,
+        }))
+        .collect::<Vec<_>>(),
+    })
+  }
+}
+
+/// A frame's pixels, and the colours they index
+type Frame<'a> = (&'a [Vec<u8>], &'a [(u8, u8, u8)]);
+
+/// Count the pixels which differ between two frames, by palette index or by
+/// colour, or `None` if none do
+fn compare_frames(
+  index: usize,
+  (first, first_colours): Frame<'_>,
+  (second, second_colours): Frame<'_>,
+  highlight: bool,
+) -> Option<FrameDiff> {
+  let pixels = first
+    .iter()
+    .zip(second)
+    .map(|(first, second)| {
+      first
+        .iter()
+        .zip(second)
+        .map(|(&first, &second)| {
+          let same_colour = first_colours.get(usize::from(first))
+            == second_colours.get(usize::from(second));
+
+          match (first, second) {
+            _ if first == second && same_colour => u8::from(first != 0),
+            (_, 0) if first != 0 => 2,
+            (0, _) if second != 0 => 3,
+            _ => 4,
+          }
+        })
+        .collect::<Vec<u8>>()
+    })
+    .collect::<Vec<_>>();
+  let changed_pixels =
+    pixels.iter().flatten().filter(|&&pixel| pixel > 1).count();
+
+  (changed_pixels > 0).then(|| FrameDiff {
+    index,
+    changed_pixels,
+    highlight: highlight
+      .then(|| IndexedFrame { pixels, colours: HIGHLIGHT_COLOURS.to_vec() }),
+  })
+}
@@ -0,0 +1,154 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use {
+  image::{DynamicImage, Rgba, RgbaImage},
+  std::{fs, io::BufWriter},
+};
+
+/// A decoded frame's palette indices alongside the colours they map to
+///
+/// Index zero is always the paper colour.
+pub struct IndexedFrame {
+  pub pixels:  Vec<Vec<u8>>,
+  pub colours: Vec<(u8, u8, u8)>,
+}
+impl IndexedFrame {
+  #[allow(clippy::cast_possible_truncation)]
+  pub fn width(&self) -> u32 { self.pixels.first().map_or(0, Vec::len) as u32 }
+
+  #[allow(clippy::cast_possible_truncation)]
+  pub const fn height(&self) -> u32 { self.pixels.len() as u32 }
+
+  /// Convert the frame to an image, optionally rendering the paper colour as
+  /// fully transparent
+  pub fn to_image(&self, transparent: bool) -> DynamicImage {
+    let image = RgbaImage::from_fn(self.width(), self.height(), |x, y| {
+      let index = self.pixels[y as usize][x as usize];
+      let (red, green, blue) = self.colours[usize::from(index)];
+
+      Rgba([red, green, blue, if transparent && index == 0 { 0 } else { 255 }])
+    });
+
+    if transparent {
+      DynamicImage::ImageRgba8(image)
+    } else {
+      DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image).into_rgb8())
+    }
+  }
+}
+
+fn flatten_colours(colours: &[(u8, u8, u8)]) -> Vec<u8> {
+  colours.iter().copied().flat_map(<[u8; 3]>::from).collect()
+}
+
+/// Encode frames as an infinitely looping GIF, keeping each frame's own
+/// palette rather than quantising RGB pixels
+///
+/// `delay` is in hundredths of a second.
+#[allow(clippy::cast_possible_truncation)]
+pub fn write_gif(
+  path: &str,
+  frames: &[IndexedFrame],
+  delay: u16,
+  transparent: bool,
+) {
+  let Some(first) = frames.first() else { return };
+  let writer = BufWriter::new(fs::File::create(path).unwrap());
+  let mut encoder =
+    gif::Encoder::new(writer, first.width() as u16, first.height() as u16, &[])
+      .unwrap();
+
+  encoder.set_repeat(gif::Repeat::Infinite).unwrap();
+
+  for frame in frames {
+    let mut gif_frame = gif::Frame::from_palette_pixels(
+      frame.width() as u16,
+      frame.height() as u16,
+      frame.pixels.concat(),
+      flatten_colours(&frame.colours),
+      transparent.then_some(0),
+    );
+
+    gif_frame.delay = delay;
+    // Transparent frames would otherwise be drawn on top of each other
+    gif_frame.dispose = gif::DisposalMethod::Background;
+
+    encoder.write_frame(&gif_frame).unwrap();
+  }
+}
+
+/// Encode frames as an infinitely looping APNG
+///
+/// PNG only allows a single palette for every frame, so each frame's colours
+/// are merged into one shared palette, with a dedicated fully transparent
+/// entry for the paper when `transparent` is set.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn write_apng(
+  path: &str,
+  frames: &[IndexedFrame],
+  framerate: f64,
+  transparent: bool,
+) {
+  let Some(first) = frames.first() else { return };
+  let mut palette = Vec::new();
+
+  if transparent {
+    palette.push((0, 0, 0));
+  }
+
+  let mut find_or_insert = |colour| {
+    palette.iter().position(|&existing| existing == colour).unwrap_or_else(
+      || {
+        palette.push(colour);
+
+        palette.len() - 1
+      },
+    ) as u8
+  };
+  let remapped = frames
+    .iter()
+    .map(|frame| {
+      let indices =
+        frame
+          .colours
+          .iter()
+          .enumerate()
+          .map(|(index, &colour)| {
+            if transparent && index == 0 { 0 } else { find_or_insert(colour) }
+          })
+          .collect::<Vec<u8>>();
+
+      frame
+        .pixels
+        .iter()
+        .flatten()
+        .map(|&pixel| indices[usize::from(pixel)])
+        .collect::<Vec<u8>>()
+    })
+    .collect::<Vec<_>>();
+  let writer = BufWriter::new(fs::File::create(path).unwrap());
+  let mut encoder = png::Encoder::new(writer, first.width(), first.height());
+
+  encoder.set_color(png::ColorType::Indexed);
+  encoder.set_depth(png::BitDepth::Eight);
+  encoder.set_palette(flatten_colours(&palette));
+
+  if transparent {
+    encoder.set_trns(vec![0]);
+  }
+
+  encoder.set_animated(frames.len() as u32, 0).unwrap();
+  // Every Flipnote framerate is a multiple of 0.5, so a denominator of ten
+  // keeps the delay exact
+  encoder.set_frame_delay(10, (framerate * 10.0) as u16).unwrap();
+  encoder.set_dispose_op(png::DisposeOp::Background).unwrap();
+
+  let mut writer = encoder.write_header().unwrap();
+
+  for pixels in remapped {
+    writer.write_image_data(&pixels).unwrap();
+  }
+
+  writer.finish().unwrap();
+}
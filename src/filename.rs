@@ -0,0 +1,199 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use std::fmt::{self, Write};
+
+/// Characters a public filename's checksum is drawn from
+const CHECKSUM_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+
+/// The check digit of a filename formatted as `<MAC>_<random>_<edits>`;
+/// the sum of its first byte as hexadecimal with the next two, and of its
+/// second through sixteenth characters, modulo 256, indexing
+/// [`CHECKSUM_ALPHABET`]
+///
+/// <https://github.com/pbsds/hatena-server/wiki/PPM-format#filenames>
+fn check_digit(name: &[u8]) -> char {
+  let first = name
+    .get(..2)
+    .and_then(|digits| std::str::from_utf8(digits).ok())
+    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+    .unwrap_or_default();
+  let sum = name
+    .iter()
+    .take(16)
+    .skip(1)
+    .fold(first, |sum, &byte| sum.wrapping_add(byte));
+
+  char::from(CHECKSUM_ALPHABET[usize::from(sum % 32)])
+}
+
+/// The last six digits of a console's MAC address, with which its author ID
+/// also ends
+fn mac_hex(mac: [u8; 3]) -> String {
+  mac.iter().fold(String::new(), |mut output, byte| {
+    let _ = write!(output, "{byte:02X}");
+
+    output
+  })
+}
+
+/// A PPM's parent or current filename
+///
+/// Filenames are stored as:
+///
+/// - three bytes representing the last six digits of the console's MAC address
+/// - thirteen-character `String`
+/// - `u16` edit counter
+///
+/// and formatted as `<three-byte MAC as hexadecimal>_<thirteen-character
+/// string>_<edit counter as three-digit number>`, e.g.,
+/// `F78DA8_14768882B56B8_030`. The public filename the console saves and
+/// shares a Flipnote under replaces the first digit with a checksum.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PpmFilename {
+  pub mac:    [u8; 3],
+  pub random: [u8; 13],
+  pub edits:  u16,
+}
+impl PpmFilename {
+  /// Decode the eighteen bytes a filename is stored as
+  pub fn from_stored(bytes: &[u8; 18]) -> Self {
+    let mut random = [0; 13];
+
+    random.copy_from_slice(&bytes[3..16]);
+
+    Self {
+      mac: [bytes[0], bytes[1], bytes[2]],
+      random,
+      edits: u16::from_le_bytes([bytes[16], bytes[17]]),
+    }
+  }
+
+  /// The checksum character which replaces the first digit of the public
+  /// filename
+  pub fn checksum(&self) -> char {
+    let mut name = self.mac_hex().into_bytes();
+
+    name.push(b'_');
+    name.extend(self.random);
+
+    check_digit(&name)
+  }
+
+  /// The filename the console saves and shares the Flipnote under
+  pub fn to_public(&self) -> String {
+    let mut public = self.to_string();
+
+    public.replace_range(..1, &self.checksum().to_string());
+
+    public
+  }
+
+  pub fn mac_hex(&self) -> String { mac_hex(self.mac) }
+
+  /// Whether the random part holds the uppercase hexadecimal digits the
+  /// console generates
+  pub fn is_well_formed(&self) -> bool {
+    self
+      .random
+      .iter()
+      .all(|byte| byte.is_ascii_digit() || (b'A'..=b'F').contains(byte))
+  }
+
+  /// Whether `name` has the shape of a public filename, and the checksum a
+  /// filename whose MAC starts with `first_digit` has
+  ///
+  /// The public form replaces the MAC's first digit, so it has to be taken
+  /// from the filename stored in the Flipnote.
+  pub fn is_valid_public(name: &str, first_digit: u8) -> bool {
+    let bytes = name.as_bytes();
+    let is_hex = |range: std::ops::Range<usize>| {
+      bytes[range].iter().all(|byte| {
+        byte.is_ascii_digit()
+          || (b'A'..=b'F').contains(&byte.to_ascii_uppercase())
+      })
+    };
+
+    if bytes.len() != 24
+      || bytes[6] != b'_'
+      || bytes[20] != b'_'
+      || !is_hex(1..6)
+      || !is_hex(7..20)
+      || !bytes[21..].iter().all(u8::is_ascii_digit)
+    {
+      return false;
+    }
+
+    let mut stored = name.to_ascii_uppercase().into_bytes();
+
+    stored[0] = first_digit.to_ascii_uppercase();
+
+    check_digit(&stored).eq_ignore_ascii_case(&char::from(bytes[0]))
+  }
+}
+impl fmt::Display for PpmFilename {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}_{}_{:03}",
+      self.mac_hex(),
+      String::from_utf8_lossy(&self.random),
+      self.edits
+    )
+  }
+}
+
+/// The first part of the root Flipnote's filename, which is all a PPM keeps
+/// of it
+///
+/// Fragments are stored as the three MAC bytes, followed by the first ten
+/// digits of the random part packed into five bytes, and formatted as, e.g.,
+/// `F78DA8_14768882B5`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FilenameFragment {
+  pub mac:    [u8; 3],
+  pub random: [u8; 5],
+}
+impl FilenameFragment {
+  pub const fn from_stored(bytes: [u8; 8]) -> Self {
+    Self {
+      mac:    [bytes[0], bytes[1], bytes[2]],
+      random: [bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]],
+    }
+  }
+
+  pub fn mac_hex(&self) -> String { mac_hex(self.mac) }
+}
+impl fmt::Display for FilenameFragment {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}_", self.mac_hex())?;
+
+    self.random.iter().try_for_each(|byte| write!(f, "{byte:02X}"))
+  }
+}
+
+/// Compare the name of the file at `path` with the public filename of the
+/// Flipnote it holds, for files named like a Flipnote
+pub fn check_file_name(
+  path: &str,
+  current_filename: &str,
+  public_filename: &str,
+) -> Option<String> {
+  let name = std::path::Path::new(path).file_stem()?.to_str()?;
+  let bytes = name.as_bytes();
+
+  if bytes.len() != 24 || bytes[6] != b'_' || bytes[20] != b'_' {
+    return None;
+  }
+
+  if !PpmFilename::is_valid_public(
+    name,
+    current_filename.bytes().next().unwrap_or_default(),
+  ) {
+    Some(format!("file name {name} has an invalid checksum"))
+  } else if !name.eq_ignore_ascii_case(public_filename) {
+    Some(format!("file name {name} does not match {public_filename}"))
+  } else {
+    None
+  }
+}
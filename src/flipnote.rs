@@ -0,0 +1,392 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use {
+  crate::{
+    export::IndexedFrame,
+    kwz::KWZParser,
+    palette::Palette,
+    ppm::{self, PPMParser, Recovery},
+  },
+  chrono::{DateTime, Local, Utc},
+  std::{
+    collections::BTreeMap,
+    fmt::Write,
+    io::Read,
+    path::{Path, PathBuf},
+  },
+};
+
+/// Metadata shared by every Flipnote format
+pub struct Metadata {
+  /// Root, parent, and current author as `(name, ID)`
+  pub authors:          [(String, String); 3],
+  pub parent_filename:  String,
+  pub current_filename: String,
+  /// The name the Flipnote is saved and shared under, for formats where it
+  /// differs from the stored filename
+  pub public_filename:  Option<String>,
+  /// Inconsistencies found in the filenames and author IDs
+  pub warnings:         Vec<String>,
+  /// When the Flipnote was last saved
+  pub timestamp:        DateTime<Utc>,
+  pub lock:             bool,
+  pub loop_:            bool,
+  pub frame_speed:      u8,
+  pub bgm_speed:        u32,
+  pub bgm_framerate:    f64,
+  pub layer_visible:    Vec<bool>,
+}
+
+/// A sound track's size in bytes and length in seconds
+pub struct SoundTrack {
+  pub name:     &'static str,
+  pub size:     u32,
+  pub duration: f64,
+}
+
+/// A decoded Flipnote, regardless of the format it was stored in
+///
+/// Frames are decoded to palette indices; zero is the paper, and the
+/// remaining values index into [`Self::get_frame_palette`].
+pub trait Flipnote {
+  /// Frame width and height in pixels
+  fn dimensions(&self) -> (usize, usize);
+
+  fn layer_count(&self) -> usize;
+
+  /// The colours the Flipnote's own app draws with
+  fn default_palette(&self) -> Palette;
+
+  fn get_frame_count(&self) -> u16;
+
+  fn get_thumb_index(&self) -> u16;
+
+  fn get_framerate(&self) -> f64;
+
+  fn get_frame_palette(
+    &mut self,
+    index: usize,
+    palette: &Palette,
+  ) -> Vec<(u8, u8, u8)>;
+
+  fn get_frame_pixels(&mut self, index: usize) -> Vec<Vec<u8>>;
+
+  /// Decode each layer of a frame onto its own canvas, keeping the palette
+  /// indices used by [`Self::get_frame_pixels`]
+  fn get_layer_pixels(&mut self, index: usize) -> Vec<Vec<Vec<u8>>>;
+
+  fn metadata(&self) -> Metadata;
+
+  /// Every metadata field of the format, as written by `dump`
+  fn metadata_json(&self) -> serde_json::Value;
+
+  fn sound_tracks(&self) -> Vec<SoundTrack>;
+
+  /// Samples per second of the sound tracks
+  fn sample_rate(&self) -> u32;
+
+  /// Decode a sound track, indexed as in [`Self::sound_tracks`], to 16-bit
+  /// PCM at [`Self::sample_rate`]; formats whose audio can't be decoded yet
+  /// decode to silence
+  fn decode_sound_track(&self, _index: usize) -> Vec<i16> { Vec::new() }
+
+  /// Size in bytes of each section of the file, in file order
+  fn get_section_sizes(&self) -> Vec<(String, u64)>;
+
+  fn file_size(&self) -> usize;
+
+  /// The file's metadata, optionally followed by the details of every frame
+  fn dump_json(&mut self, extended: bool) -> serde_json::Value;
+
+  /// Write [`Self::dump_json`] to a file
+  fn dump_to_json(&mut self, filename: &str, extended: bool) {
+    let json = self.dump_json(extended);
+    let writer =
+      std::io::BufWriter::new(std::fs::File::create(filename).unwrap());
+
+    serde_json::to_writer_pretty(writer, &json).unwrap();
+  }
+
+  /// Playback length in seconds
+  fn get_duration(&self) -> f64 {
+    f64::from(self.get_frame_count()) / self.get_framerate()
+  }
+
+  /// Metadata, sound, and section details as shown by `para info`
+  fn info_json(&self) -> serde_json::Value {
+    let mut json = self.metadata_json();
+    let (width, height) = self.dimensions();
+
+    json["file_size"] = self.file_size().into();
+    json["width"] = width.into();
+    json["height"] = height.into();
+    json["layer_count"] = self.layer_count().into();
+    json["duration"] = self.get_duration().into();
+    json["sound"] = self
+      .sound_tracks()
+      .into_iter()
+      .map(|track| {
+        (
+          track.name.to_string(),
+          serde_json::json!({ "size": track.size, "duration": track.duration }),
+        )
+      })
+      .collect::<serde_json::Map<_, _>>()
+      .into();
+    json["sections"] = self
+      .get_section_sizes()
+      .into_iter()
+      .map(|(name, size)| (name, size.into()))
+      .collect::<serde_json::Map<_, _>>()
+      .into();
+
+    json
+  }
+
+  /// Human-readable summary as shown by `para info`
+  fn summary(&self) -> String {
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    let metadata = self.metadata();
+    let sound_tracks = self.sound_tracks();
+    let (width, height) = self.dimensions();
+    let mut summary = String::new();
+
+    write_authorship(&mut summary, &metadata);
+    let _ = writeln!(
+      summary,
+      "{:<22}{width}x{height}, {} layers",
+      "dimensions",
+      self.layer_count()
+    );
+    let _ = writeln!(
+      summary,
+      "{:<22}{} (thumbnail {})",
+      "frames",
+      self.get_frame_count(),
+      self.get_thumb_index()
+    );
+    let _ = writeln!(
+      summary,
+      "{:<22}{} ({} fps), bgm {} ({} fps)",
+      "speed",
+      metadata.frame_speed,
+      self.get_framerate(),
+      metadata.bgm_speed,
+      metadata.bgm_framerate
+    );
+    let _ = writeln!(summary, "{:<22}{:.2} s", "duration", self.get_duration());
+    let _ = writeln!(summary, "{:<22}{}", "locked", yes_no(metadata.lock));
+    let _ = writeln!(summary, "{:<22}{}", "loop", yes_no(metadata.loop_));
+    let _ = writeln!(
+      summary,
+      "{:<22}{}",
+      "visible",
+      metadata
+        .layer_visible
+        .iter()
+        .zip(1..)
+        .map(|(&visible, layer)| format!("layer {layer} {}", yes_no(visible)))
+        .collect::<Vec<_>>()
+        .join(", ")
+    );
+    let _ = writeln!(
+      summary,
+      "{:<22}{}",
+      "sound",
+      yes_no(sound_tracks.iter().any(|track| track.size > 0))
+    );
+    for track in sound_tracks {
+      let _ = writeln!(summary, "  {:<20}{:.2} s", track.name, track.duration);
+    }
+
+    let _ = writeln!(summary, "sections ({} bytes)", self.file_size());
+    for (section, size) in self.get_section_sizes() {
+      let _ = writeln!(summary, "  {section:<20}{size} bytes");
+    }
+
+    summary
+  }
+}
+
+/// Write the authors, filenames, and timestamp of a Flipnote as shown by
+/// `para info`
+pub fn write_authorship(summary: &mut String, metadata: &Metadata) {
+  let _ = writeln!(summary, "authors");
+  for (role, (name, id)) in
+    ["root", "parent", "current"].iter().zip(&metadata.authors)
+  {
+    let _ = writeln!(summary, "  {role:<20}{name} ({id})");
+  }
+
+  let _ = writeln!(summary, "filenames");
+  let _ = writeln!(summary, "  {:<20}{}", "parent", metadata.parent_filename);
+  let _ = writeln!(summary, "  {:<20}{}", "current", metadata.current_filename);
+  if let Some(public_filename) = &metadata.public_filename {
+    let _ = writeln!(summary, "  {:<20}{public_filename}", "public");
+  }
+  for issue in &metadata.warnings {
+    let _ = writeln!(summary, "  {:<20}{issue}", "warning");
+  }
+  let _ = writeln!(
+    summary,
+    "{:<22}{}",
+    "timestamp",
+    metadata.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S %Z")
+  );
+}
+
+impl dyn Flipnote + '_ {
+  /// Iterate over every frame in playback order, decoded with `palette`
+  pub fn frames(&mut self, palette: Palette, layers: bool) -> Frames<'_> {
+    Frames { flipnote: self, palette, layers, index: 0 }
+  }
+}
+
+/// The bitmaps of each frame of a Flipnote; the composited frame, or each
+/// layer on its own canvas when `layers` is set
+pub struct Frames<'a> {
+  flipnote: &'a mut dyn Flipnote,
+  palette:  Palette,
+  layers:   bool,
+  index:    usize,
+}
+impl Iterator for Frames<'_> {
+  type Item = Vec<IndexedFrame>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.index >= usize::from(self.flipnote.get_frame_count()) {
+      return None;
+    }
+
+    let colours = self.flipnote.get_frame_palette(self.index, &self.palette);
+    let bitmaps = if self.layers {
+      self.flipnote.get_layer_pixels(self.index)
+    } else {
+      vec![self.flipnote.get_frame_pixels(self.index)]
+    };
+
+    self.index += 1;
+
+    Some(
+      bitmaps
+        .into_iter()
+        .map(|pixels| IndexedFrame { pixels, colours: colours.clone() })
+        .collect(),
+    )
+  }
+
+  fn nth(&mut self, n: usize) -> Option<Self::Item> {
+    // Parsers can seek to any frame, so skipped frames needn't be decoded
+    self.index = self.index.saturating_add(n);
+
+    self.next()
+  }
+}
+
+/// The first four bytes of a file, which identify every Hatena and Flipnote
+/// format
+pub fn read_magic(path: &str) -> Option<[u8; 4]> {
+  let mut magic = [0; 4];
+
+  std::fs::File::open(path)
+    .and_then(|mut file| file.read_exact(&mut magic))
+    .ok()
+    .map(|()| magic)
+}
+
+/// Open a Flipnote of any supported format, detected by its magic
+pub fn open(path: &str) -> Result<Box<dyn Flipnote>, String> {
+  let bytes = std::fs::read(path)
+    .map_err(|error| format!("could not read {path}: {error}"))?;
+
+  from_bytes(bytes)
+    .ok_or_else(|| format!("unsupported format({path}), expected a Flipnote"))
+}
+
+/// Load a Flipnote of any supported format from memory, or `None` if its
+/// magic isn't one, or if it's a KWZ missing the sections it needs
+pub fn from_bytes(bytes: Vec<u8>) -> Option<Box<dyn Flipnote>> {
+  Some(match bytes.get(..4) {
+    Some(b"PARA") => {
+      let mut parser = PPMParser::new(bytes);
+
+      parser.load();
+
+      Box::new(parser)
+    }
+    Some([b'K', b'F', b'H', _]) => {
+      let mut parser = KWZParser::new(bytes);
+
+      parser.load().ok()?;
+
+      Box::new(parser)
+    }
+    _ => return None,
+  })
+}
+
+/// Frames substituted in recovery mode, by index, and why
+pub type DamagedFrames = BTreeMap<usize, String>;
+
+/// Open a Flipnote leniently, decoding as much of a damaged file as possible
+/// and returning which frames were substituted and why
+///
+/// Only PPMs can be recovered, so other formats are opened as usual.
+pub fn open_recovering(
+  path: &str,
+  recovery: Recovery,
+) -> Result<(Box<dyn Flipnote>, DamagedFrames), String> {
+  let bytes = std::fs::read(path)
+    .map_err(|error| format!("could not read {path}: {error}"))?;
+
+  if bytes.starts_with(b"PARA") {
+    let mut parser = PPMParser::new(bytes);
+    let damaged_frames = parser.load_recovering(recovery).clone();
+
+    return Ok((Box::new(parser), damaged_frames));
+  }
+
+  open(path).map(|flipnote| (flipnote, BTreeMap::new()))
+}
+
+/// Read the metadata of a Flipnote of any supported format, or of a `.tmb`
+pub fn read_metadata(path: &str) -> Result<Metadata, String> {
+  if ppm::is_tmb(path) {
+    let mut parser = PPMParser::new_from_file(path);
+
+    parser.load_metadata();
+
+    return Ok(parser.metadata());
+  }
+
+  open(path).map(|flipnote| flipnote.metadata())
+}
+
+/// Every file in a directory and its subdirectories, in a stable order
+///
+/// Symbolic links to files are kept, but links to directories aren't
+/// followed, so a link back up the tree can't make the walk recurse forever.
+pub fn walk(directory: &Path) -> Vec<PathBuf> {
+  let mut entries = std::fs::read_dir(directory)
+    .into_iter()
+    .flatten()
+    .flatten()
+    .filter_map(|entry| Some((entry.path(), entry.file_type().ok()?)))
+    .collect::<Vec<_>>();
+
+  entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+  entries
+    .into_iter()
+    .flat_map(|(path, file_type)| {
+      if file_type.is_dir() {
+        walk(&path)
+      } else if file_type.is_symlink() && !path.is_file() {
+        Vec::new()
+      } else {
+        vec![path]
+      }
+    })
+    .collect()
+}
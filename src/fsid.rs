@@ -0,0 +1,42 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use {serde::Serialize, std::fmt};
+
+/// A Flipnote Studio ID, identifying the console a PPM's author made it on
+///
+/// IDs are stored as eight little-endian bytes, and were displayed by
+/// Flipnote Hatena as sixteen hexadecimal digits, most significant first,
+/// e.g., `9C03A1301E17A554`. The last six digits are those of the console's
+/// MAC address, which also start its filenames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(into = "String")]
+pub struct FlipnoteStudioId([u8; 8]);
+impl FlipnoteStudioId {
+  pub const fn from_stored(bytes: [u8; 8]) -> Self { Self(bytes) }
+
+  /// Whether the ID has the structure of one issued by a console; its first
+  /// digit is `0`, `1`, `5`, or `9`, and its eighth digit is `0`
+  pub fn is_valid(self) -> bool {
+    let id = self.to_string();
+
+    matches!(id.as_bytes()[0], b'0' | b'1' | b'5' | b'9')
+      && id.as_bytes()[7] == b'0'
+  }
+
+  /// Whether the ID is the placeholder left when an author isn't recorded,
+  /// which is all zeroes
+  pub fn is_placeholder(self) -> bool { self.0 == [0; 8] }
+
+  /// The last six digits of the MAC address of the console the ID belongs
+  /// to
+  pub fn mac_hex(self) -> String { self.to_string()[10..].to_string() }
+}
+impl fmt::Display for FlipnoteStudioId {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.0.iter().rev().try_for_each(|byte| write!(f, "{byte:02X}"))
+  }
+}
+impl From<FlipnoteStudioId> for String {
+  fn from(id: FlipnoteStudioId) -> Self { id.to_string() }
+}
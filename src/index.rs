@@ -0,0 +1,245 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use {
+  crate::{
+    flipnote::{Flipnote, Metadata, from_bytes, walk},
+    ppm::{self, PPMParser},
+  },
+  chrono::{DateTime, NaiveDate},
+  serde::{Deserialize, Serialize},
+  sha1::{Digest, Sha1},
+  std::{
+    fmt::Write as _,
+    fs,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+  },
+};
+
+/// The metadata of one file of a collection, as stored in an index
+///
+/// Fields which `.tmb` files don't hold are left empty for them.
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+  pub path:                String,
+  pub format:              String,
+  pub root_author_name:    String,
+  pub root_author_id:      String,
+  pub parent_author_name:  String,
+  pub parent_author_id:    String,
+  pub current_author_name: String,
+  pub current_author_id:   String,
+  pub parent_filename:     String,
+  pub current_filename:    String,
+  /// RFC 3339
+  pub timestamp:           String,
+  pub frame_count:         u16,
+  /// As its format stores it; 1 to 8 for PPMs, and 0 to 10 for KWZs
+  pub frame_speed:         Option<u8>,
+  /// Frames per second, which compares across formats
+  pub framerate:           Option<f64>,
+  /// Seconds
+  pub duration:            Option<f64>,
+  pub lock:                bool,
+  pub sound:               Option<bool>,
+  /// SHA-1 of the whole file, as hexadecimal
+  pub sha1:                String,
+}
+impl Entry {
+  fn new(path: &str, format: &str, metadata: Metadata, bytes: &[u8]) -> Self {
+    let [
+      (root_author_name, root_author_id),
+      (parent_author_name, parent_author_id),
+      (current_author_name, current_author_id),
+    ] = metadata.authors;
+
+    Self {
+      path: path.to_string(),
+      format: format.to_string(),
+      root_author_name,
+      root_author_id,
+      parent_author_name,
+      parent_author_id,
+      current_author_name,
+      current_author_id,
+      parent_filename: metadata.parent_filename,
+      current_filename: metadata.current_filename,
+      timestamp: metadata.timestamp.to_rfc3339(),
+      frame_count: 0,
+      frame_speed: Some(metadata.frame_speed),
+      framerate: None,
+      duration: None,
+      lock: metadata.lock,
+      sound: None,
+      sha1: Sha1::digest(bytes).iter().fold(
+        String::new(),
+        |mut output, byte| {
+          let _ = write!(output, "{byte:02x}");
+
+          output
+        },
+      ),
+    }
+  }
+
+  /// Read a file's metadata, or `None` if it isn't a Flipnote or `.tmb`
+  pub fn read(path: &str) -> Option<Self> {
+    let bytes = fs::read(path).ok()?;
+
+    if ppm::is_tmb_bytes(&bytes) {
+      let mut parser = PPMParser::new(bytes.clone());
+
+      parser.load_metadata();
+
+      return Some(Self {
+        frame_count: parser.get_frame_count(),
+        // The animation header, which holds the speed, isn't in a `.tmb`
+        frame_speed: None,
+        ..Self::new(path, "tmb", parser.metadata(), &bytes)
+      });
+    }
+
+    let flipnote = from_bytes(bytes.clone())?;
+
+    Some(Self {
+      frame_count: flipnote.get_frame_count(),
+      framerate: Some(flipnote.get_framerate()),
+      duration: Some(flipnote.get_duration()),
+      sound: Some(flipnote.sound_tracks().iter().any(|track| track.size > 0)),
+      ..Self::new(
+        path,
+        if bytes.starts_with(b"PARA") { "ppm" } else { "kwz" },
+        flipnote.metadata(),
+        &bytes,
+      )
+    })
+  }
+
+  const fn authors(&self) -> [&String; 6] {
+    [
+      &self.root_author_name,
+      &self.root_author_id,
+      &self.parent_author_name,
+      &self.parent_author_id,
+      &self.current_author_name,
+      &self.current_author_id,
+    ]
+  }
+}
+
+/// Index every Flipnote and `.tmb` in a directory and its subdirectories
+pub fn build(directory: &Path) -> Vec<Entry> {
+  walk(directory)
+    .iter()
+    .filter_map(|path| Entry::read(&path.to_string_lossy()))
+    .collect()
+}
+
+/// Write an index as JSON lines, one entry per line
+pub fn write(entries: &[Entry], path: &str) -> Result<(), String> {
+  let mut writer = BufWriter::new(
+    fs::File::create(path)
+      .map_err(|error| format!("could not write {path}: {error}"))?,
+  );
+
+  for entry in entries {
+    serde_json::to_writer(&mut writer, entry)
+      .map_err(|error| format!("could not write {path}: {error}"))?;
+    writeln!(writer)
+      .map_err(|error| format!("could not write {path}: {error}"))?;
+  }
+
+  Ok(())
+}
+
+pub fn read(path: &str) -> Result<Vec<Entry>, String> {
+  let file = fs::File::open(path)
+    .map_err(|error| format!("could not read {path}: {error}"))?;
+
+  BufReader::new(file)
+    .lines()
+    .map(|line| line.map_err(|error| format!("could not read {path}: {error}")))
+    .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+    .map(|line| {
+      serde_json::from_str(&line?)
+        .map_err(|error| format!("invalid index({path}): {error}"))
+    })
+    .collect()
+}
+
+/// A condition on the entries of an index, given as `key=value`
+pub enum Filter {
+  /// Any author's name or ID contains the value, ignoring case
+  Author(String),
+  /// The parent or current filename contains the value, ignoring case
+  Filename(String),
+  After(NaiveDate),
+  Before(NaiveDate),
+  /// Frames per second, so that PPMs and KWZs, whose speeds differ, compare
+  Framerate(f64),
+  MinFrames(u16),
+  /// Seconds
+  MinDuration(f64),
+  Lock(bool),
+  Sound(bool),
+  Sha1(String),
+}
+impl Filter {
+  pub fn parse(filter: &str) -> Result<Self, String> {
+    let invalid = || format!("invalid filter({filter})");
+    let (key, value) = filter.split_once('=').ok_or_else(invalid)?;
+    let date =
+      || NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| invalid());
+    let yes_no = || match value {
+      "yes" | "true" => Ok(true),
+      "no" | "false" => Ok(false),
+      _ => Err(invalid()),
+    };
+
+    Ok(match key {
+      "author" => Self::Author(value.to_lowercase()),
+      "filename" => Self::Filename(value.to_lowercase()),
+      "after" => Self::After(date()?),
+      "before" => Self::Before(date()?),
+      "framerate" => Self::Framerate(value.parse().map_err(|_| invalid())?),
+      "min_frames" => Self::MinFrames(value.parse().map_err(|_| invalid())?),
+      "min_duration" =>
+        Self::MinDuration(value.parse().map_err(|_| invalid())?),
+      "lock" => Self::Lock(yes_no()?),
+      "sound" => Self::Sound(yes_no()?),
+      "sha1" => Self::Sha1(value.to_lowercase()),
+      _ => return Err(invalid()),
+    })
+  }
+
+  /// Whether an entry meets the condition; an entry without the field being
+  /// filtered on doesn't
+  pub fn matches(&self, entry: &Entry) -> bool {
+    let date = DateTime::parse_from_rfc3339(&entry.timestamp)
+      .ok()
+      .map(|timestamp| timestamp.date_naive());
+
+    match self {
+      Self::Author(author) => entry
+        .authors()
+        .iter()
+        .any(|field| field.to_lowercase().contains(author)),
+      Self::Filename(filename) =>
+        [&entry.parent_filename, &entry.current_filename]
+          .iter()
+          .any(|field| field.to_lowercase().contains(filename)),
+      Self::After(after) => date.is_some_and(|date| date >= *after),
+      Self::Before(before) => date.is_some_and(|date| date <= *before),
+      Self::Framerate(framerate) => entry
+        .framerate
+        .is_some_and(|rate| (rate - framerate).abs() < f64::EPSILON),
+      Self::MinFrames(frames) => entry.frame_count >= *frames,
+      Self::MinDuration(duration) =>
+        entry.duration.is_some_and(|length| length >= *duration),
+      Self::Lock(lock) => entry.lock == *lock,
+      Self::Sound(sound) => entry.sound == Some(*sound),
+      Self::Sha1(sha1) => entry.sha1.starts_with(sha1.as_str()),
+    }
+  }
+}
@@ -0,0 +1,818 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use {
+  crate::{
+    adpcm,
+    flipnote::{Flipnote, Metadata, SoundTrack},
+    palette::Palette,
+  },
+  byteorder::{LittleEndian, ReadBytesExt},
+  chrono::{DateTime, TimeZone, Utc},
+  std::{
+    collections::HashMap,
+    fmt::Write,
+    io::{Cursor, Read},
+    sync::OnceLock,
+  },
+};
+
+const WIDTH: usize = 320;
+const HEIGHT: usize = 240;
+
+/// Flipnote speed -> frames per second
+const FRAMERATES: [f64; 11] =
+  [0.2, 0.5, 1.0, 2.0, 4.0, 6.0, 8.0, 12.0, 20.0, 24.0, 30.0];
+
+/// Indices into the line table of the 32 most common lines
+const COMMON_LINE_INDICES: [u16; 32] = [
+  0x0000, 0x0CD0, 0x19A0, 0x02D9, 0x088B, 0x0051, 0x00F3, 0x0009, 0x001B,
+  0x0001, 0x0003, 0x05B2, 0x1116, 0x00A2, 0x01E6, 0x0012, 0x0036, 0x0002,
+  0x0006, 0x0B64, 0x08DC, 0x0144, 0x00FC, 0x0024, 0x001C, 0x0004, 0x0334,
+  0x099C, 0x0668, 0x1338, 0x1004, 0x166C,
+];
+
+type LineTables = (Vec<[u8; 8]>, Vec<[u8; 8]>);
+
+/// Every possible eight-pixel line, alongside the same line rotated left by
+/// one pixel
+///
+/// <https://github.com/Flipnote-Collective/flipnote-studio-3d-docs/wiki/kwz-format#kmc-frame-data>
+#[allow(clippy::many_single_char_names)]
+fn line_tables() -> &'static LineTables {
+  static LINE_TABLES: OnceLock<LineTables> = OnceLock::new();
+
+  LINE_TABLES.get_or_init(|| {
+    let mut lines = Vec::with_capacity(6561);
+    let mut shifted_lines = Vec::with_capacity(6561);
+
+    // Each pixel is one of three values, so a line is an eight-digit base
+    // three number, with pairs of digits swapped
+    for index in 0..6561 {
+      let digit = |place: u32| (index / 3_u16.pow(7 - place) % 3) as u8;
+      let [a, b, c, d, e, f, g, h] = [0, 1, 2, 3, 4, 5, 6, 7].map(digit);
+
+      lines.push([b, a, d, c, f, e, h, g]);
+      shifted_lines.push([a, d, c, f, e, h, g, b]);
+    }
+
+    (lines, shifted_lines)
+  })
+}
+
+fn strip_null(string: &str) -> String { string.replace(char::from(0), "") }
+
+/// Read `n` bytes, of which any past the end of the stream read as zeroes
+fn read_n_to_vec(stream: &mut Cursor<Vec<u8>>, n: usize) -> Vec<u8> {
+  let mut buffer = vec![0; n];
+
+  // A cursor reads everything it holds, up to the length of the buffer
+  let _ = stream.read(&mut buffer);
+
+  buffer
+}
+
+fn vec_u8_to_string(vec: &[u8]) -> String {
+  vec.iter().rev().fold(String::new(), |mut output, m| {
+    let _ = write!(output, "{m:02X}");
+
+    output
+  })
+}
+
+/// Author names are stored as eleven UTF-16 characters
+fn read_wide_string(stream: &mut Cursor<Vec<u8>>) -> String {
+  let characters = (0..11)
+    .map(|_| stream.read_u16::<LittleEndian>().unwrap_or_default())
+    .collect::<Vec<u16>>();
+
+  strip_null(&String::from_utf16_lossy(&characters))
+}
+
+fn timestamp_from_2000(seconds: u32) -> DateTime<Utc> {
+  // Timestamps are stored as the number of seconds since 2000, January, 1st,
+  // so we add 946684800 to convert them to Unix timestamps
+  Utc
+    .timestamp_opt(i64::from(seconds) + 946_684_800, 0)
+    .single()
+    .unwrap_or_default()
+}
+
+/// Per-frame metadata from the KMI section
+///
+/// <https://github.com/Flipnote-Collective/flipnote-studio-3d-docs/wiki/kwz-format#kmi-frame-meta>
+#[derive(Clone, Default)]
+pub struct FrameMeta {
+  /// Paper colour, layer visibility, and the two pens of each layer
+  pub flags:         u32,
+  pub layer_sizes:   [u16; 3],
+  pub author_id:     String,
+  pub layer_depths:  [u8; 3],
+  /// Sound effects one through four, packed into the lowest four bits
+  pub sound_effects: u8,
+  pub camera_flags:  u32,
+}
+impl FrameMeta {
+  pub fn to_json(&self, index: usize, offset: u64) -> serde_json::Value {
+    serde_json::json!({
+      "index": index,
+      "offset": offset,
+      "flags": self.flags,
+      "paper_colour": self.flags & 0xF,
+      "layer_sizes": self.layer_sizes,
+      "layer_depths": self.layer_depths,
+      "author_id": self.author_id,
+      "se1": self.sound_effects & 0x1 != 0,
+      "se2": self.sound_effects >> 1 & 0x1 != 0,
+      "se3": self.sound_effects >> 2 & 0x1 != 0,
+      "se4": self.sound_effects >> 3 & 0x1 != 0,
+      "camera_flags": self.camera_flags,
+    })
+  }
+}
+
+/// Decoder for Flipnote Studio 3D's `.kwz` format
+///
+/// <https://github.com/Flipnote-Collective/flipnote-studio-3d-docs/wiki/kwz-format>
+pub struct KWZParser {
+  stream:              Cursor<Vec<u8>>,
+  /// Section magic -> (offset, size)
+  sections:            HashMap<[u8; 3], (u64, u32)>,
+  layers:              Vec<Vec<u8>>,
+  prev_frame_index:    Option<usize>,
+  frame_meta:          Vec<FrameMeta>,
+  frame_offsets:       Vec<u64>,
+  creation_timestamp:  DateTime<Utc>,
+  modified_timestamp:  DateTime<Utc>,
+  app_version:         u32,
+  root_author_id:      String,
+  parent_author_id:    String,
+  current_author_id:   String,
+  root_author_name:    String,
+  parent_author_name:  String,
+  current_author_name: String,
+  root_filename:       String,
+  parent_filename:     String,
+  current_filename:    String,
+  thumb_index:         u16,
+  lock:                bool,
+  loop_:               bool,
+  frame_speed:         u8,
+  framerate:           f64,
+  layer_visible:       [bool; 3],
+  bgm_speed:           u32,
+  bgm_framerate:       f64,
+  sound_sizes:         [u32; 5],
+  bit_index:           i32,
+  bit_value:           u32,
+}
+impl KWZParser {
+  pub fn new(stream: Vec<u8>) -> Self {
+    Self { stream: Cursor::new(stream), ..Self::default() }
+  }
+
+  #[allow(unused)]
+  pub fn new_from_file(file: &str) -> Self {
+    Self {
+      stream: Cursor::new(std::fs::read(file).unwrap()),
+      ..Self::default()
+    }
+  }
+
+  /// Fails if the file header, frame metadata, or frame data section is
+  /// missing; truncated sections read as zeroes
+  pub fn load(&mut self) -> Result<(), String> {
+    self.read_sections();
+    self.read_meta()?;
+    self.read_frame_meta()?;
+    self.read_sound_header();
+    self.layers = vec![vec![0; WIDTH * HEIGHT]; 3];
+    self.prev_frame_index = None;
+
+    Ok(())
+  }
+
+  fn section(&self, magic: [u8; 3]) -> Result<(u64, u32), String> {
+    self.sections.get(&magic).copied().ok_or_else(|| {
+      format!("missing {} section", String::from_utf8_lossy(&magic))
+    })
+  }
+
+  /// Sections are stored back-to-back, each starting with a four-byte magic
+  /// and a `u32` size
+  fn read_sections(&mut self) {
+    let length = self.stream.get_ref().len() as u64;
+    let mut offset = 0;
+
+    self.stream.set_position(0);
+
+    while offset + 8 <= length {
+      let magic = read_n_to_vec(&mut self.stream, 4);
+      let size = self.stream.read_u32::<LittleEndian>().unwrap_or_default();
+
+      // The file ends with a signature rather than another section
+      if !magic.starts_with(b"K") {
+        break;
+      }
+
+      self.sections.insert([magic[0], magic[1], magic[2]], (offset, size));
+
+      offset += 8 + u64::from(size);
+
+      self.stream.set_position(offset);
+    }
+  }
+
+  /// Decode metadata
+  ///
+  /// <https://github.com/Flipnote-Collective/flipnote-studio-3d-docs/wiki/kwz-format#kfh-file-header>
+  fn read_meta(&mut self) -> Result<(), String> {
+    let (offset, _) = self.section(*b"KFH")?;
+
+    // Skip the section header and CRC32
+    self.stream.set_position(offset + 12);
+
+    self.creation_timestamp = timestamp_from_2000(
+      self.stream.read_u32::<LittleEndian>().unwrap_or_default(),
+    );
+    self.modified_timestamp = timestamp_from_2000(
+      self.stream.read_u32::<LittleEndian>().unwrap_or_default(),
+    );
+    self.app_version =
+      self.stream.read_u32::<LittleEndian>().unwrap_or_default();
+    self.root_author_id =
+      vec_u8_to_string(&read_n_to_vec(&mut self.stream, 10));
+    self.parent_author_id =
+      vec_u8_to_string(&read_n_to_vec(&mut self.stream, 10));
+    self.current_author_id =
+      vec_u8_to_string(&read_n_to_vec(&mut self.stream, 10));
+    self.root_author_name = read_wide_string(&mut self.stream);
+    self.parent_author_name = read_wide_string(&mut self.stream);
+    self.current_author_name = read_wide_string(&mut self.stream);
+    self.root_filename = strip_null(&String::from_utf8_lossy(&read_n_to_vec(
+      &mut self.stream,
+      28,
+    )));
+    self.parent_filename = strip_null(&String::from_utf8_lossy(
+      &read_n_to_vec(&mut self.stream, 28),
+    ));
+    self.current_filename = strip_null(&String::from_utf8_lossy(
+      &read_n_to_vec(&mut self.stream, 28),
+    ));
+
+    let _frame_count =
+      self.stream.read_u16::<LittleEndian>().unwrap_or_default();
+
+    self.thumb_index =
+      self.stream.read_u16::<LittleEndian>().unwrap_or_default();
+
+    let flags = self.stream.read_u16::<LittleEndian>().unwrap_or_default();
+
+    self.lock = flags & 0x1 != 0;
+    self.loop_ = (flags >> 1) & 0x1 != 0;
+    self.frame_speed = self.stream.read_u8().unwrap_or_default();
+    // Speeds out of range, as damaged files have, play at the slowest speed
+    self.framerate = FRAMERATES
+      .get(usize::from(self.frame_speed))
+      .copied()
+      .unwrap_or(FRAMERATES[0]);
+
+    // Layers are hidden when their flag is set
+    let layer_flags = self.stream.read_u8().unwrap_or_default();
+
+    self.layer_visible =
+      [0, 1, 2].map(|layer| (layer_flags >> layer) & 0x1 == 0);
+
+    Ok(())
+  }
+
+  /// Frame metadata is stored as a table of 28-byte entries, and frame data is
+  /// stored back-to-back in the KMC section in the same order
+  ///
+  /// Only entries held by the file are read, however large the section claims
+  /// to be, and no more than a `u16` can count.
+  fn read_frame_meta(&mut self) -> Result<(), String> {
+    let (meta_offset, meta_size) = self.section(*b"KMI")?;
+    let (data_offset, _) = self.section(*b"KMC")?;
+    let available =
+      (self.stream.get_ref().len() as u64).saturating_sub(meta_offset + 8);
+    let entries =
+      (u64::from(meta_size).min(available) / 28).min(u64::from(u16::MAX));
+    // Skip the section header and CRC32
+    let mut frame_offset = data_offset + 12;
+
+    self.stream.set_position(meta_offset + 8);
+    self.frame_meta.clear();
+    self.frame_offsets.clear();
+
+    for _ in 0..entries {
+      let flags = self.stream.read_u32::<LittleEndian>().unwrap_or_default();
+      let layer_sizes = [0; 3].map(|_: u8| {
+        self.stream.read_u16::<LittleEndian>().unwrap_or_default()
+      });
+      let author_id = vec_u8_to_string(&read_n_to_vec(&mut self.stream, 10));
+      let layer_depths =
+        [0; 3].map(|_: u8| self.stream.read_u8().unwrap_or_default());
+      let sound_effects = self.stream.read_u8().unwrap_or_default();
+      let camera_flags =
+        self.stream.read_u32::<LittleEndian>().unwrap_or_default();
+
+      self.frame_offsets.push(frame_offset);
+      frame_offset += layer_sizes.iter().copied().map(u64::from).sum::<u64>();
+      self.frame_meta.push(FrameMeta {
+        flags,
+        layer_sizes,
+        author_id,
+        layer_depths,
+        sound_effects,
+        camera_flags,
+      });
+    }
+
+    Ok(())
+  }
+
+  /// <https://github.com/Flipnote-Collective/flipnote-studio-3d-docs/wiki/kwz-format#ksn-sound-data>
+  fn read_sound_header(&mut self) {
+    let Some(&(offset, _)) = self.sections.get(b"KSN") else { return };
+
+    self.stream.set_position(offset + 8);
+
+    self.bgm_speed = self.stream.read_u32::<LittleEndian>().unwrap_or_default();
+    self.bgm_framerate =
+      FRAMERATES.get(self.bgm_speed as usize).copied().unwrap_or_default();
+
+    // BGM, followed by sound effects one, two, three, and four
+    for size in &mut self.sound_sizes {
+      *size = self.stream.read_u32::<LittleEndian>().unwrap_or_default();
+    }
+  }
+
+  /// A sound track's data; tracks are stored one after another, after the
+  /// sound header and its CRC32, and one which runs past the end of the file
+  /// is cut short
+  fn sound_track_bytes(&self, index: usize) -> &[u8] {
+    let bytes = self.stream.get_ref();
+    let (Some(&(offset, _)), Some(&size)) =
+      (self.sections.get(b"KSN"), self.sound_sizes.get(index))
+    else {
+      return &[];
+    };
+    let start = offset
+      + 36
+      + self.sound_sizes[..index].iter().copied().map(u64::from).sum::<u64>();
+    let start = usize::try_from(start).unwrap_or(usize::MAX).min(bytes.len());
+    let end = start
+      .saturating_add(usize::try_from(size).unwrap_or(usize::MAX))
+      .min(bytes.len());
+
+    &bytes[start..end]
+  }
+
+  /// Layer data is read as a little-endian stream of 16-bit words, consumed
+  /// from the least significant bit up
+  fn read_bits(&mut self, count: i32) -> u32 {
+    if self.bit_index + count > 16 {
+      let next_bits =
+        u32::from(self.stream.read_u16::<LittleEndian>().unwrap_or_default());
+
+      // The index briefly goes negative when the bits straddle two words
+      self.bit_value |= next_bits << (16 - self.bit_index);
+      self.bit_index -= 16;
+    }
+
+    let result = self.bit_value & ((1 << count) - 1);
+
+    self.bit_value >>= count;
+    self.bit_index += count;
+
+    result
+  }
+
+  /// Whether a frame can be decoded without first decoding the previous one
+  ///
+  /// A layer's diffing flag is cleared when it is based on the previous
+  /// frame. Frames past the frame metadata have nothing to be based on.
+  fn frame_is_new(&self, index: usize) -> bool {
+    index == 0
+      || self
+        .frame_meta
+        .get(index)
+        .is_none_or(|meta| (meta.flags >> 4) & 0x07 == 0x07)
+  }
+
+  fn read_frame(&mut self, index: usize) -> &Vec<Vec<u8>> {
+    if self.prev_frame_index == Some(index) {
+      return &self.layers;
+    }
+
+    // Unless the frame before it was the last decoded, decode from the closest
+    // frame that doesn't depend on the ones before it, which starts from blank
+    // layers
+    let start = if index != 0 && self.prev_frame_index == Some(index - 1) {
+      index
+    } else {
+      (0..=index).rev().find(|&frame| self.frame_is_new(frame)).unwrap_or(0)
+    };
+
+    for frame in start..=index {
+      self.decode_frame(frame);
+    }
+
+    &self.layers
+  }
+
+  /// Decode a frame's layers on top of the previously decoded frame, or onto
+  /// blank layers if it doesn't depend on it
+  ///
+  /// Each layer is split into 128x128 tiles, which are each split into 8x8
+  /// tiles that are encoded as combinations of eight-pixel lines.
+  #[allow(clippy::too_many_lines)]
+  fn decode_frame(&mut self, index: usize) {
+    let (lines, shifted_lines) = line_tables();
+    let common =
+      |index: u32| lines[usize::from(COMMON_LINE_INDICES[index as usize])];
+    let common_shifted = |index: u32| {
+      shifted_lines[usize::from(COMMON_LINE_INDICES[index as usize])]
+    };
+    // Line indices are thirteen bits, but there are only 6561 lines, so
+    // damaged files can index past them; those lines are left blank
+    let line =
+      |index: u32| lines.get(index as usize).copied().unwrap_or_default();
+    let line_shifted = |index: u32| {
+      shifted_lines.get(index as usize).copied().unwrap_or_default()
+    };
+    self.prev_frame_index = Some(index);
+
+    // Tiles a frame skips are blank unless it's based on the one before it
+    if self.frame_is_new(index) {
+      self.layers.fill(vec![0; WIDTH * HEIGHT]);
+    }
+
+    // Frames the frame metadata has no entry for have no data to decode
+    let (Some(meta), Some(&offset)) =
+      (self.frame_meta.get(index).cloned(), self.frame_offsets.get(index))
+    else {
+      return;
+    };
+    let mut layer_offset = offset;
+
+    for layer in 0..3 {
+      let layer_size = meta.layer_sizes[layer];
+
+      self.stream.set_position(layer_offset);
+
+      layer_offset += u64::from(layer_size);
+
+      // A 38-byte layer only skips tiles, so it is unchanged from the previous
+      // frame
+      if layer_size == 38 {
+        continue;
+      }
+
+      self.bit_index = 16;
+      self.bit_value = 0;
+
+      let mut skip_tiles = 0;
+
+      for large_tile_y in (0..HEIGHT).step_by(128) {
+        for large_tile_x in (0..WIDTH).step_by(128) {
+          for tile_y in (large_tile_y..large_tile_y + 128).step_by(8) {
+            if tile_y >= HEIGHT {
+              break;
+            }
+
+            for tile_x in (large_tile_x..large_tile_x + 128).step_by(8) {
+              if tile_x >= WIDTH {
+                break;
+              }
+
+              if skip_tiles > 0 {
+                skip_tiles -= 1;
+
+                continue;
+              }
+
+              let tile = match self.read_bits(3) {
+                // Every line is the same common line
+                0 => [common(self.read_bits(5)); 8],
+                // Every line is the same line
+                1 => [line(self.read_bits(13)); 8],
+                // Alternating common line and its shifted version
+                2 => {
+                  let line_index = self.read_bits(5);
+                  let (a, b) = (common(line_index), common_shifted(line_index));
+
+                  [a, b, a, b, a, b, a, b]
+                }
+                // Alternating line and its shifted version
+                3 => {
+                  let line_index = self.read_bits(13);
+                  let (a, b) = (line(line_index), line_shifted(line_index));
+
+                  [a, b, a, b, a, b, a, b]
+                }
+                // Each line is either a common line or any line, chosen by a
+                // mask
+                4 => {
+                  let mask = self.read_bits(8);
+                  let mut tile = [[0; 8]; 8];
+
+                  for (row, pixels) in tile.iter_mut().enumerate() {
+                    *pixels = if mask & (1 << row) == 0 {
+                      line(self.read_bits(13))
+                    } else {
+                      common(self.read_bits(5))
+                    };
+                  }
+
+                  tile
+                }
+                // Skip this tile and the next few
+                5 => {
+                  skip_tiles = self.read_bits(5);
+
+                  continue;
+                }
+                // Two lines arranged in one of four patterns
+                7 => {
+                  let mut pattern = self.read_bits(2);
+                  let (a, b) = if self.read_bits(1) == 1 {
+                    pattern = (pattern + 1) % 4;
+
+                    (common(self.read_bits(5)), common(self.read_bits(5)))
+                  } else {
+                    (line(self.read_bits(13)), line(self.read_bits(13)))
+                  };
+
+                  match pattern {
+                    0 => [a, b, a, b, a, b, a, b],
+                    1 => [a, a, b, a, a, b, a, a],
+                    2 => [a, b, a, a, b, a, a, b],
+                    _ => [a, b, b, a, b, b, a, b],
+                  }
+                }
+                // Type six is never used
+                _ => continue,
+              };
+
+              for (row, pixels) in tile.iter().enumerate() {
+                let start = (tile_y + row) * WIDTH + tile_x;
+
+                self.layers[layer][start..start + 8].copy_from_slice(pixels);
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+}
+impl Flipnote for KWZParser {
+  fn dimensions(&self) -> (usize, usize) { (WIDTH, HEIGHT) }
+
+  fn layer_count(&self) -> usize { 3 }
+
+  fn default_palette(&self) -> Palette { Palette::N3DS }
+
+  #[allow(clippy::cast_possible_truncation)]
+  fn get_frame_count(&self) -> u16 { self.frame_meta.len() as u16 }
+
+  fn get_thumb_index(&self) -> u16 { self.thumb_index }
+
+  fn get_framerate(&self) -> f64 { self.framerate }
+
+  /// Colours for a frame; the paper, followed by the two pens of each layer
+  fn get_frame_palette(
+    &mut self,
+    index: usize,
+    palette: &Palette,
+  ) -> Vec<(u8, u8, u8)> {
+    let flags = self.frame_meta.get(index).map_or(0, |meta| meta.flags);
+    let colour = |value: u32| match value & 0xF {
+      1 => palette.black,
+      2 => palette.red,
+      3 => palette.yellow,
+      4 => palette.green,
+      5 => palette.blue,
+      _ => palette.white,
+    };
+
+    vec![
+      colour(flags),
+      colour(flags >> 8),
+      colour(flags >> 12),
+      colour(flags >> 16),
+      colour(flags >> 20),
+      colour(flags >> 24),
+      colour(flags >> 28),
+    ]
+  }
+
+  fn get_layer_pixels(&mut self, index: usize) -> Vec<Vec<Vec<u8>>> {
+    self
+      .read_frame(index)
+      .iter()
+      .zip((0..).step_by(2))
+      .map(|(layer, colour_offset)| {
+        layer
+          .chunks(WIDTH)
+          .map(|line| {
+            line
+              .iter()
+              .map(|&pixel| if pixel > 0 { colour_offset + pixel } else { 0 })
+              .collect()
+          })
+          .collect()
+      })
+      .collect()
+  }
+
+  /// Composite a frame's layers, drawing deeper layers first
+  fn get_frame_pixels(&mut self, index: usize) -> Vec<Vec<u8>> {
+    let depths =
+      self.frame_meta.get(index).map_or([0; 3], |meta| meta.layer_depths);
+    let mut order = [2, 1, 0];
+
+    order.sort_by_key(|&layer| std::cmp::Reverse(depths[layer]));
+
+    let layers = self.get_layer_pixels(index);
+    let mut pixels = vec![vec![0u8; WIDTH]; HEIGHT];
+
+    for layer in order {
+      for (y, line) in layers[layer].iter().enumerate() {
+        for (x, &pixel) in line.iter().enumerate() {
+          if pixel > 0 {
+            pixels[y][x] = pixel;
+          }
+        }
+      }
+    }
+
+    pixels
+  }
+
+  fn metadata(&self) -> Metadata {
+    Metadata {
+      authors:          [
+        (self.root_author_name.clone(), self.root_author_id.clone()),
+        (self.parent_author_name.clone(), self.parent_author_id.clone()),
+        (self.current_author_name.clone(), self.current_author_id.clone()),
+      ],
+      parent_filename:  self.parent_filename.clone(),
+      current_filename: self.current_filename.clone(),
+      public_filename:  None,
+      warnings:         Vec::new(),
+      timestamp:        self.modified_timestamp,
+      lock:             self.lock,
+      loop_:            self.loop_,
+      frame_speed:      self.frame_speed,
+      bgm_speed:        self.bgm_speed,
+      bgm_framerate:    self.bgm_framerate,
+      layer_visible:    self.layer_visible.to_vec(),
+    }
+  }
+
+  fn metadata_json(&self) -> serde_json::Value {
+    serde_json::json!({
+      "sections": self
+        .sections
+        .iter()
+        .map(|(magic, (offset, size))| {
+          (
+            String::from_utf8_lossy(magic).to_string(),
+            serde_json::json!({ "offset": offset, "size": size }),
+          )
+        })
+        .collect::<serde_json::Map<_, _>>(),
+      "creation_timestamp": self.creation_timestamp.to_string(),
+      "modified_timestamp": self.modified_timestamp.to_string(),
+      "app_version": self.app_version,
+      "frame_count": self.frame_meta.len(),
+      "thumb_index": self.thumb_index,
+      "lock": self.lock,
+      "loop": self.loop_,
+      "root_author_name": self.root_author_name,
+      "parent_author_name": self.parent_author_name,
+      "current_author_name": self.current_author_name,
+      "root_author_id": self.root_author_id,
+      "parent_author_id": self.parent_author_id,
+      "current_author_id": self.current_author_id,
+      "root_filename": self.root_filename,
+      "parent_filename": self.parent_filename,
+      "current_filename": self.current_filename,
+      "layer_1_visible": self.layer_visible[0],
+      "layer_2_visible": self.layer_visible[1],
+      "layer_3_visible": self.layer_visible[2],
+      "frame_speed": self.frame_speed,
+      "bgm_speed": self.bgm_speed,
+      "framerate": self.framerate,
+      "bgm_framerate": self.bgm_framerate,
+      "bgm_size": self.sound_sizes[0],
+      "se1_size": self.sound_sizes[1],
+      "se2_size": self.sound_sizes[2],
+      "se3_size": self.sound_sizes[3],
+      "se4_size": self.sound_sizes[4],
+    })
+  }
+
+  /// Audio is stored as ADPCM at 16364 Hz with samples of two or four bits,
+  /// so tracks are decoded to measure them
+  #[allow(clippy::cast_precision_loss)]
+  fn sound_tracks(&self) -> Vec<SoundTrack> {
+    ["bgm", "se1", "se2", "se3", "se4"]
+      .into_iter()
+      .zip(self.sound_sizes)
+      .enumerate()
+      .map(|(index, (name, size))| SoundTrack {
+        name,
+        size,
+        duration: adpcm::decode_kwz(self.sound_track_bytes(index)).len() as f64
+          / 16364.0,
+      })
+      .collect()
+  }
+
+  fn sample_rate(&self) -> u32 { 16364 }
+
+  fn decode_sound_track(&self, index: usize) -> Vec<i16> {
+    adpcm::decode_kwz(self.sound_track_bytes(index))
+  }
+
+  /// Sections are named by their magic, and include their eight-byte header
+  fn get_section_sizes(&self) -> Vec<(String, u64)> {
+    let mut sections = self.sections.iter().collect::<Vec<_>>();
+
+    sections.sort_by_key(|(_, (offset, _))| *offset);
+
+    let end = sections
+      .last()
+      .map_or(0, |(_, (offset, size))| offset + 8 + u64::from(*size));
+    let mut sizes = sections
+      .into_iter()
+      .map(|(magic, (_, size))| {
+        (String::from_utf8_lossy(magic).to_string(), 8 + u64::from(*size))
+      })
+      .collect::<Vec<_>>();
+
+    sizes.push((
+      "signature".to_string(),
+      (self.stream.get_ref().len() as u64).saturating_sub(end),
+    ));
+
+    sizes
+  }
+
+  fn file_size(&self) -> usize { self.stream.get_ref().len() }
+
+  fn dump_json(&mut self, extended: bool) -> serde_json::Value {
+    let mut json = self.metadata_json();
+
+    if extended {
+      json["frames"] = self
+        .frame_meta
+        .iter()
+        .zip(&self.frame_offsets)
+        .enumerate()
+        .map(|(index, (meta, &offset))| meta.to_json(index, offset))
+        .collect();
+    }
+
+    json
+  }
+}
+impl Default for KWZParser {
+  fn default() -> Self {
+    Self {
+      stream:              Cursor::default(),
+      sections:            HashMap::new(),
+      layers:              Vec::new(),
+      prev_frame_index:    None,
+      frame_meta:          Vec::new(),
+      frame_offsets:       Vec::new(),
+      creation_timestamp:  timestamp_from_2000(0),
+      modified_timestamp:  timestamp_from_2000(0),
+      app_version:         Default::default(),
+      root_author_id:      String::default(),
+      parent_author_id:    String::default(),
+      current_author_id:   String::default(),
+      root_author_name:    String::default(),
+      parent_author_name:  String::default(),
+      current_author_name: String::default(),
+      root_filename:       String::default(),
+      parent_filename:     String::default(),
+      current_filename:    String::default(),
+      thumb_index:         Default::default(),
+      lock:                Default::default(),
+      loop_:               Default::default(),
+      frame_speed:         Default::default(),
+      framerate:           Default::default(),
+      layer_visible:       Default::default(),
+      bgm_speed:           Default::default(),
+      bgm_framerate:       Default::default(),
+      sound_sizes:         Default::default(),
+      bit_index:           16,
+      bit_value:           0,
+    }
+  }
+}
@@ -0,0 +1,40 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! The decoders behind `para`, for tools which embed them, such as the fuzz
+//! targets
+
+#![feature(decl_macro, coroutines, coroutine_trait)]
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+#![allow(
+  clippy::must_use_candidate,
+  clippy::missing_errors_doc,
+  clippy::missing_panics_doc
+)]
+#![recursion_limit = "128"]
+
+pub mod adpcm;
+pub mod check;
+pub mod dedupe;
+pub mod diff;
+pub mod export;
+pub mod filename;
+pub mod flipnote;
+pub mod fsid;
+pub mod index;
+pub mod kwz;
+pub mod lineage;
+pub mod npf;
+pub mod palette;
+pub mod ppm;
+pub mod scale;
+pub mod signature;
+pub mod ugo;
@@ -0,0 +1,151 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use {
+  crate::flipnote::{read_metadata, walk},
+  std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::Path,
+  },
+};
+
+/// A Flipnote in a derivation tree; either one held by the collection, or a
+/// parent only known from the metadata of the Flipnotes edited from it
+#[derive(Default)]
+pub struct Node {
+  /// Author as `(name, ID)`
+  pub author:      (String, String),
+  pub root_author: (String, String),
+  /// Where the collection holds the Flipnote, if anywhere
+  pub paths:       Vec<String>,
+}
+
+/// Which Flipnotes of a collection were edited from which, keyed by their
+/// current filenames
+///
+/// A Flipnote records the filename of the one it was edited from, which is
+/// its own when it wasn't edited from another.
+#[derive(Default)]
+pub struct Lineage {
+  nodes: BTreeMap<String, Node>,
+  /// `(parent, child)` filenames
+  edges: BTreeSet<(String, String)>,
+}
+impl Lineage {
+  /// Read the metadata of every Flipnote in a directory and its
+  /// subdirectories, skipping files which aren't Flipnotes
+  pub fn from_directory(directory: &Path) -> Self {
+    let mut lineage = Self::default();
+
+    for path in walk(directory) {
+      let path = path.to_string_lossy();
+      let Ok(metadata) = read_metadata(&path) else {
+        continue;
+      };
+      let [root_author, parent_author, current_author] = metadata.authors;
+      let node =
+        lineage.nodes.entry(metadata.current_filename.clone()).or_default();
+
+      node.author = current_author;
+      node.root_author = root_author.clone();
+      node.paths.push(path.to_string());
+
+      if metadata.parent_filename != metadata.current_filename {
+        let parent =
+          lineage.nodes.entry(metadata.parent_filename.clone()).or_default();
+
+        // A parent the collection holds knows its own authors
+        if parent.paths.is_empty() {
+          parent.author = parent_author;
+          parent.root_author = root_author;
+        }
+
+        lineage
+          .edges
+          .insert((metadata.parent_filename, metadata.current_filename));
+      }
+    }
+
+    lineage
+  }
+
+  /// Filenames of the Flipnotes which weren't edited from any other known
+  /// Flipnote
+  fn roots(&self) -> Vec<&String> {
+    self
+      .nodes
+      .keys()
+      .filter(|filename| {
+        !self.edges.iter().any(|(_, child)| child == *filename)
+      })
+      .collect()
+  }
+
+  pub fn to_json(&self) -> serde_json::Value {
+    serde_json::json!({
+      "nodes": self
+        .nodes
+        .iter()
+        .map(|(filename, node)| serde_json::json!({
+          "filename": filename,
+          "edits": edit_count(filename),
+          "author_name": node.author.0,
+          "author_id": node.author.1,
+          "root_author_name": node.root_author.0,
+          "root_author_id": node.root_author.1,
+          "present": !node.paths.is_empty(),
+          "paths": node.paths,
+        }))
+        .collect::<Vec<_>>(),
+      "edges": self
+        .edges
+        .iter()
+        .map(|(parent, child)| serde_json::json!({
+          "parent": parent,
+          "child": child,
+        }))
+        .collect::<Vec<_>>(),
+      "roots": self.roots(),
+    })
+  }
+
+  /// Render the tree as a Graphviz graph; Flipnotes the collection doesn't
+  /// hold are drawn dashed
+  pub fn to_dot(&self) -> String {
+    let mut dot = String::new();
+
+    let _ = writeln!(dot, "digraph lineage {{");
+    let _ = writeln!(dot, "  rankdir=LR;");
+    let _ = writeln!(dot, "  node [shape=box, fontname=monospace];");
+    for (filename, node) in &self.nodes {
+      let _ = writeln!(
+        dot,
+        "  \"{}\" [label=\"{}\\n{} ({}){}\"{}];",
+        escape(filename),
+        escape(filename),
+        escape(&node.author.0),
+        escape(&node.author.1),
+        edit_count(filename)
+          .map_or_else(String::new, |edits| format!("\\nedits {edits}")),
+        if node.paths.is_empty() { ", style=dashed" } else { "" }
+      );
+    }
+    for (parent, child) in &self.edges {
+      let _ =
+        writeln!(dot, "  \"{}\" -> \"{}\";", escape(parent), escape(child));
+    }
+    let _ = writeln!(dot, "}}");
+
+    dot
+  }
+}
+
+/// The edit counter which ends a PPM's filename; KWZ filenames have none
+fn edit_count(filename: &str) -> Option<u16> {
+  filename.rsplit_once('_')?.1.parse().ok()
+}
+
+fn escape(text: &str) -> String {
+  text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -1,7 +1,6 @@
 // Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
 // SPDX-License-Identifier: MIT
 
-#![feature(decl_macro, coroutines, coroutine_trait)]
 #![deny(
   warnings,
   nonstandard_style,
@@ -13,30 +12,699 @@
 #![deny(clippy::all, clippy::nursery, clippy::pedantic)]
 #![recursion_limit = "128"]
 
-mod ppm;
-
-use {crate::ppm::PPMParser, image::DynamicImage, std::process::exit};
-
-#[allow(unused)]
-fn get_image(parser: &mut PPMParser, index: usize) -> DynamicImage {
-  let frame = parser.get_frame_pixels(index);
-  let colours = parser.get_frame_palette(index);
-  let mut img = Vec::new();
-  let mut img_encoder = image::codecs::bmp::BmpEncoder::new(&mut img);
-
-  img_encoder.encode_with_palette(
-    &frame.into_iter().flatten().collect::<Vec<u8>>(),
-    256,
-    192,
-    image::ColorType::L8,
-    Some(&[
-      [colours[0].0, colours[0].1, colours[0].2],
-      [colours[1].0, colours[1].1, colours[1].2],
-      [colours[2].0, colours[2].1, colours[2].2],
-    ]),
+use {
+  para_cli::{
+    check, dedupe, diff,
+    export::{self, IndexedFrame},
+    filename,
+    flipnote::{self, Flipnote},
+    index, lineage,
+    npf::{self, NPFParser},
+    palette::Palette,
+    ppm::{self, PPMParser},
+    scale::Scaling,
+    signature,
+    ugo::{self, UGOParser},
+  },
+  std::{fmt::Write, path::Path, process::exit},
+};
+
+/// Decode a frame into the bitmaps that should be written for it; the
+/// composited frame, or each layer on its own canvas with `--layers`
+fn get_frames(
+  parser: &mut dyn Flipnote,
+  index: usize,
+  options: &Options,
+) -> Vec<IndexedFrame> {
+  let palette = options.palette.unwrap_or_else(|| parser.default_palette());
+
+  parser
+    .frames(palette, options.layers)
+    .nth(index)
+    .unwrap_or_default()
+    .into_iter()
+    .map(|frame| IndexedFrame {
+      pixels: options.scaling.apply(frame.pixels),
+      ..frame
+    })
+    .collect()
+}
+
+/// The paths rendered output is written to; one per layer with `--layers`,
+/// e.g., `out_layer1.png` and `out_layer2.png`
+fn output_paths(
+  out_path: &str,
+  layer_count: usize,
+  options: &Options,
+) -> Vec<String> {
+  if !options.layers {
+    return vec![out_path.to_string()];
+  }
+
+  let path = Path::new(out_path);
+  let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+  let extension = path
+    .extension()
+    .map(|extension| format!(".{}", extension.to_string_lossy()))
+    .unwrap_or_default();
+
+  (1..=layer_count)
+    .map(|layer| {
+      path
+        .with_file_name(format!("{stem}_layer{layer}{extension}"))
+        .display()
+        .to_string()
+    })
+    .collect()
+}
+
+fn save_frames(
+  parser: &mut dyn Flipnote,
+  index: usize,
+  out_path: &str,
+  options: &Options,
+) {
+  for (out_path, frame) in output_paths(out_path, parser.layer_count(), options)
+    .iter()
+    .zip(get_frames(parser, index, options))
+  {
+    frame.to_image(options.transparent).save(out_path).unwrap();
+  }
+}
+
+/// Remove a boolean `--flag` from the arguments, returning whether it was
+/// present
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+  args.iter().position(|arg| arg == flag).is_some_and(|position| {
+    args.remove(position);
+
+    true
+  })
+}
+
+/// Remove an `--option <value>` pair from the arguments, returning the value
+fn take_option(args: &mut Vec<String>, option: &str) -> Option<String> {
+  let position = args.iter().position(|arg| arg == option)?;
+
+  args.remove(position);
+
+  if position < args.len() { Some(args.remove(position)) } else { None }
+}
+
+/// Options shared by every output mode
+struct Options {
+  scaling:     Scaling,
+  /// Defaults to the colours of the input's format
+  palette:     Option<Palette>,
+  transparent: bool,
+  layers:      bool,
+  extended:    bool,
+  /// Width of `.npf` and `.nbf` images, which don't store their dimensions
+  width:       Option<usize>,
+  /// Substitute damaged frames rather than failing to decode them
+  recovery:    Option<ppm::Recovery>,
+}
+impl Options {
+  fn take_from(args: &mut Vec<String>) -> Result<Self, String> {
+    let smooth = take_flag(args, "--smooth");
+    let layers = take_flag(args, "--layers");
+
+    Ok(Self {
+      scaling: Scaling::from_options(
+        &take_option(args, "--scale").unwrap_or_else(|| "1".to_string()),
+        smooth,
+      )?,
+      palette: take_option(args, "--palette")
+        .map(|palette| Palette::parse(&palette))
+        .transpose()?,
+      // Layers are only useful for compositing if they can be overlaid
+      transparent: take_flag(args, "--transparent") || layers,
+      layers,
+      extended: take_flag(args, "--extended"),
+      width: take_option(args, "--width")
+        .map(|width| {
+          width
+            .parse::<usize>()
+            .ok()
+            .filter(|&width| width > 0)
+            .ok_or_else(|| format!("invalid width({width})"))
+        })
+        .transpose()?,
+      recovery: take_option(args, "--recover")
+        .map(|recovery| ppm::Recovery::parse(&recovery))
+        .transpose()?,
+    })
+  }
+}
+
+fn print_usage(program: &str) {
+  println!(
+    "{}, version {}(1)-{}-({})-{}\n\
+    usage:  {} <in(.ppm|.kwz|.tmb|.ugo|.npf|.nbf)> <index option> <out> \
+    [options]\n\
+    \t{5} info <in> [--json]\n\
+    \t{5} signature <in(.ppm)> [--key <public key>] [--strip <out>] \
+    [--zero <out>]\n\
+    \t{5} lineage <directory> <out(.dot|.json)>\n\
+    \t{5} index <directory> <out(.jsonl)>\n\
+    \t{5} search <index(.jsonl)> [key=value ...] [--json]\n\
+    \t{5} dedupe <directory> [--threshold <bits>] [--json]\n\
+    \t{5} diff <a> <b> [--images <directory>] [--json]\n\
+    \t{5} check <in(.ppm)...> [--json]\n\
+    search filters:\n\
+           \tauthor, filename, after, before (YYYY-MM-DD), framerate, \
+           min_frames,\n\
+           \tmin_duration (seconds), lock, sound (yes, no), sha1\n\
+    index options:\n\
+           \tgif\n\
+           \tapng\n\
+           \tthumb\n\
+           \tdump\n\
+           \thtml (.ugo)\n\
+           \trender (.npf, .nbf)\n\
+           \tinteger(u16)\n\
+    options:\n\
+           \t--scale <1..4>\tupscale renders by an integer factor\n\
+           \t--smooth\tsmooth upscaled renders with Scale2x/Scale3x\n\
+           \t--palette <p>\tdsi, 3ds, hatena, high-contrast, greyscale, a \
+           palette file,\n\
+           \t\t\tor black,white,red,blue[,yellow,green] as hexadecimal \
+           colours\n\
+           \t--transparent\trender the paper colour as transparent\n\
+           \t--layers\trender each layer to its own transparent output\n\
+           \t--extended\tinclude per-frame details in dumps\n\
+           \t--width <n>\twidth of .npf and .nbf images, guessed by \
+           default\n\
+           \t--recover <blank|repeat>\tsubstitute the frames of a damaged \
+           .ppm which\n\
+           \t\t\tcan't be decoded with blank frames or the frame before \
+           them\n\n\
+           {0} home page: <https://github.com/Usugata/{0}>",
+    env!("CARGO_PKG_NAME"),
+    env!("CARGO_PKG_VERSION"),
+    env!("PROFILE"),
+    env!("TARGET"),
+    env!("GIT_COMMIT_HASH"),
+    program,
+  );
+}
+
+/// Print a summary of a Flipnote's metadata to the terminal
+fn info(path: &str, json: bool) {
+  let (mut json_info, summary, metadata) = if ppm::is_tmb(path) {
+    let parser = open_tmb(path);
+
+    (parser.header_json(), parser.header_summary(), parser.metadata())
+  } else {
+    let parser = open_or_exit(path);
+
+    (parser.info_json(), parser.summary(), parser.metadata())
+  };
+  // Archived Flipnotes are usually named by their public filename, which a
+  // corrupted or misattributed file won't match
+  let file_name_issue =
+    metadata.public_filename.as_ref().and_then(|public_filename| {
+      filename::check_file_name(
+        path,
+        &metadata.current_filename,
+        public_filename,
+      )
+    });
+
+  if json {
+    if let (Some(issue), Some(issues)) =
+      (file_name_issue, json_info["filename_issues"].as_array_mut())
+    {
+      issues.push(issue.into());
+    }
+
+    println!("{}", serde_json::to_string_pretty(&json_info).unwrap());
+  } else {
+    print!("{path}\n{summary}");
+
+    if let Some(issue) = file_name_issue {
+      println!("{:<22}{issue}", "warning");
+    }
+  }
+}
+
+/// Write the derivation tree of a directory of Flipnotes as JSON, or as a
+/// Graphviz graph
+fn lineage(directory: &str, out_path: &str) {
+  let lineage = lineage::Lineage::from_directory(Path::new(directory));
+  let is_json = Path::new(out_path)
+    .extension()
+    .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+  let output = if is_json {
+    serde_json::to_string_pretty(&lineage.to_json()).unwrap()
+  } else {
+    lineage.to_dot()
+  };
+
+  if let Err(error) = std::fs::write(out_path, output) {
+    println!("could not write {out_path}: {error}");
+    exit(1);
+  }
+
+  println!("wrote the lineage of {directory} to {out_path}");
+}
+
+/// Index the metadata of every Flipnote in a directory as JSON lines
+fn index(directory: &str, out_path: &str) {
+  let entries = index::build(Path::new(directory));
+
+  if let Err(error) = index::write(&entries, out_path) {
+    println!("{error}");
+    exit(1);
+  }
+
+  println!("indexed {} files of {directory} to {out_path}", entries.len());
+}
+
+/// Print the entries of an index which meet every filter; their paths, or
+/// the entries themselves as JSON lines with `--json`
+fn search(args: &mut Vec<String>) {
+  let json = take_flag(args, "--json");
+  let filters = match args[3..]
+    .iter()
+    .map(|filter| index::Filter::parse(filter))
+    .collect::<Result<Vec<_>, _>>()
+  {
+    Ok(filters) => filters,
+    Err(error) => {
+      println!("{error}");
+      exit(1);
+    }
+  };
+  let entries = index::read(&args[2]).unwrap_or_else(|error| {
+    println!("{error}");
+    exit(1);
+  });
+
+  for entry in entries
+    .iter()
+    .filter(|entry| filters.iter().all(|filter| filter.matches(entry)))
+  {
+    if json {
+      println!("{}", serde_json::to_string(entry).unwrap());
+    } else {
+      println!(
+        "{}\t{}\t{}",
+        entry.path, entry.current_filename, entry.current_author_name
+      );
+    }
+  }
+}
+
+/// Report the Flipnotes of a directory whose frames are identical, or
+/// within `--threshold` differing bits per frame of each other
+fn dedupe(args: &mut Vec<String>) {
+  let json = take_flag(args, "--json");
+  let threshold = match take_option(args, "--threshold")
+    .map_or(Ok(8.0), |threshold| threshold.parse::<f64>())
+  {
+    Ok(threshold) if threshold >= 0.0 => threshold,
+    _ => {
+      println!("--threshold must be a number of bits(0..64)");
+      exit(1);
+    }
+  };
+  let fingerprints = dedupe::fingerprint_directory(Path::new(&args[2]));
+  let groups = dedupe::group(&fingerprints, threshold);
+  let members = |group: &dedupe::Group| {
+    let first = &fingerprints[group.members[0]];
+
+    group
+      .members
+      .iter()
+      .map(|&member| {
+        let fingerprint = &fingerprints[member];
+
+        serde_json::json!({
+          "path": fingerprint.path,
+          "exact": first.exact == fingerprint.exact,
+          "current_filename": fingerprint.metadata["current_filename"],
+          "current_author_name": fingerprint.metadata["current_author_name"],
+          "distance": first.distance(fingerprint),
+          "differences": first.metadata_differences(fingerprint),
+        })
+      })
+      .collect::<Vec<_>>()
+  };
+
+  if json {
+    let groups = groups
+      .iter()
+      .map(|group| serde_json::json!({ "members": members(group) }))
+      .collect::<Vec<_>>();
+
+    println!(
+      "{}",
+      serde_json::to_string_pretty(&serde_json::json!({ "groups": groups }))
+        .unwrap()
+    );
+
+    return;
+  }
+
+  for (group, number) in groups.iter().zip(1..) {
+    println!("group {number}");
+
+    for member in members(group) {
+      let differences = member["differences"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(serde_json::Value::as_str)
+        .collect::<Vec<_>>();
+
+      println!(
+        "  {}\t{}\t{}\t{}{}",
+        member["path"].as_str().unwrap_or_default(),
+        member["current_filename"].as_str().unwrap_or_default(),
+        member["current_author_name"].as_str().unwrap_or_default(),
+        if member["exact"].as_bool().unwrap_or_default() {
+          "exact".to_string()
+        } else {
+          format!(
+            "distance {:.2}",
+            member["distance"].as_f64().unwrap_or_default()
+          )
+        },
+        if differences.is_empty() {
+          String::new()
+        } else {
+          format!(", differs in {}", differences.join(", "))
+        }
+      );
+    }
+  }
+
+  println!(
+    "{} groups of duplicates among {} Flipnotes",
+    groups.len(),
+    fingerprints.len()
   );
+}
+
+/// Print what changed between two Flipnotes, optionally writing an image of
+/// each changed frame with its changed ink highlighted
+fn diff(args: &mut Vec<String>) {
+  let json = take_flag(args, "--json");
+  let images = take_option(args, "--images");
+  let mut first = open_or_exit(&args[2]);
+  let mut second = open_or_exit(&args[3]);
+  let diff = diff::Diff::new(first.as_mut(), second.as_mut(), images.is_some());
+
+  if let Some(images) = &images {
+    std::fs::create_dir_all(images).unwrap();
+
+    for frame in &diff.frames {
+      if let Some(highlight) = &frame.highlight {
+        highlight
+          .to_image(false)
+          .save(Path::new(images).join(format!("frame_{:03}.png", frame.index)))
+          .unwrap();
+      }
+    }
+  }
+
+  if json {
+    println!("{}", serde_json::to_string_pretty(&diff.to_json()).unwrap());
+
+    return;
+  }
+
+  println!("{} -> {}", args[2], args[3]);
+  println!("metadata");
+  for (field, first, second) in &diff.metadata {
+    println!("  {field:<20}{first} -> {second}");
+  }
 
-  image::load_from_memory(&img).unwrap()
+  let (first_count, second_count) = diff.frame_counts;
+
+  println!("{:<22}{first_count} -> {second_count}", "frames");
+  if second_count > first_count {
+    println!("  {:<20}{first_count}..{second_count}", "added");
+  } else if first_count > second_count {
+    println!("  {:<20}{second_count}..{first_count}", "removed");
+  }
+
+  if !diff.comparable {
+    println!("  dimensions differ, so frames weren't compared");
+  }
+
+  for frame in &diff.frames {
+    println!(
+      "  {:<20}{} pixels changed",
+      format!("frame {}", frame.index),
+      frame.changed_pixels
+    );
+  }
+
+  if let Some(images) = images {
+    println!("wrote {} highlighted frames to {images}", diff.frames.len());
+  }
+}
+
+/// Run every structural check on each PPM, printing the problems found with
+/// their offsets, and exiting with `2` if there were any
+fn check(args: &mut Vec<String>) {
+  let json = take_flag(args, "--json");
+  let results = args[2..]
+    .iter()
+    .map(|path| {
+      let issues = std::fs::read(path).map_or_else(
+        |error| {
+          vec![check::Issue {
+            offset:  0,
+            message: format!("could not read: {error}"),
+          }]
+        },
+        |bytes| check::check(&bytes),
+      );
+
+      (path, issues)
+    })
+    .collect::<Vec<_>>();
+
+  if json {
+    let results = results
+      .iter()
+      .map(|(path, issues)| {
+        serde_json::json!({
+          "path": path,
+          "ok": issues.is_empty(),
+          "issues": issues
+            .iter()
+            .map(|issue| serde_json::json!({
+              "offset": issue.offset,
+              "message": issue.message,
+            }))
+            .collect::<Vec<_>>(),
+        })
+      })
+      .collect::<Vec<_>>();
+
+    println!("{}", serde_json::to_string_pretty(&results).unwrap());
+  } else {
+    for (path, issues) in &results {
+      if issues.is_empty() {
+        println!("{path}: ok");
+      }
+
+      for issue in issues {
+        println!("{path}: 0x{:08X}: {}", issue.offset, issue.message);
+      }
+    }
+  }
+
+  if results.iter().any(|(_, issues)| !issues.is_empty()) {
+    exit(2);
+  }
+}
+
+fn open_tmb(path: &str) -> PPMParser {
+  let mut parser = PPMParser::new_from_file(path);
+
+  parser.load_metadata();
+
+  parser
+}
+
+/// `.tmb` files only hold metadata and a thumbnail, so only the `thumb` and
+/// `dump` index options apply to them
+fn convert_tmb(path: &str, index: &str, out_path: &str, options: &Options) {
+  let mut parser = open_tmb(path);
+
+  match index {
+    "thumb" => {
+      let thumbnail = parser.get_thumbnail();
+
+      IndexedFrame {
+        pixels: options.scaling.apply(thumbnail.pixels),
+        ..thumbnail
+      }
+      .to_image(options.transparent)
+      .save(out_path)
+      .unwrap();
+    }
+    "dump" => {
+      let writer =
+        std::io::BufWriter::new(std::fs::File::create(out_path).unwrap());
+
+      serde_json::to_writer_pretty(writer, &parser.header_json()).unwrap();
+    }
+    _ => {
+      println!(
+        "invalid index option({index}) for {path}, .tmb files only support \
+         thumb and dump"
+      );
+      exit(1);
+    }
+  }
+}
+
+/// `.ugo` menus can be dumped as JSON or rendered as an HTML page
+fn convert_ugo(path: &str, index: &str, out_path: &str) {
+  let mut parser = UGOParser::new_from_file(path);
+
+  if let Err(error) = parser.load() {
+    println!("{error}");
+    exit(1);
+  }
+
+  match index {
+    "dump" => parser.dump_to_json(out_path),
+    "html" => parser.dump_to_html(out_path),
+    _ => {
+      println!(
+        "invalid index option({index}) for {path}, .ugo files only support \
+         dump and html"
+      );
+      exit(1);
+    }
+  }
+}
+
+/// `.npf` and `.nbf` images only hold a single bitmap to render
+fn convert_hatena_image(
+  path: &str,
+  index: &str,
+  out_path: &str,
+  options: &Options,
+) {
+  if index != "render" {
+    println!(
+      "invalid index option({index}) for {path}, .npf and .nbf files only \
+       support render"
+    );
+    exit(1);
+  }
+
+  let mut parser = NPFParser::new_from_file(path);
+
+  if let Err(error) = parser.load() {
+    println!("{error}");
+    exit(1);
+  }
+
+  let frame = parser.get_frame(options.width);
+
+  IndexedFrame { pixels: options.scaling.apply(frame.pixels), ..frame }
+    .to_image(parser.is_transparent() || options.transparent)
+    .save(out_path)
+    .unwrap();
+}
+
+/// Open a Flipnote in recovery mode, listing the frames which couldn't be
+/// salvaged
+fn open_recovering(path: &str, recovery: ppm::Recovery) -> Box<dyn Flipnote> {
+  let (parser, damaged_frames) = flipnote::open_recovering(path, recovery)
+    .unwrap_or_else(|error| {
+      println!("{error}");
+      exit(1);
+    });
+  let frame_count = usize::from(parser.get_frame_count());
+
+  println!(
+    "salvaged {} of {frame_count} frames",
+    frame_count.saturating_sub(damaged_frames.len())
+  );
+  for reason in damaged_frames.values() {
+    println!("  substituted {reason}");
+  }
+
+  parser
+}
+
+fn open_or_exit(path: &str) -> Box<dyn Flipnote> {
+  flipnote::open(path).unwrap_or_else(|error| {
+    println!("{error}");
+    exit(1);
+  })
+}
+
+/// Report whether a PPM is signed, verifying the signature against `--key`,
+/// and write a copy of the PPM without it with `--strip` or `--zero`
+fn signature(path: &str, args: &mut Vec<String>) {
+  if flipnote::read_magic(path).is_none_or(|magic| &magic != b"PARA") {
+    println!("unsupported format({path}), expected a .ppm");
+    exit(1);
+  }
+
+  let key = take_option(args, "--key");
+  let strip = take_option(args, "--strip");
+  let zero = take_option(args, "--zero");
+  let mut parser = PPMParser::new_from_file(path);
+
+  parser.load();
+
+  let signature = parser.get_signature();
+
+  println!("{path}");
+  println!(
+    "{:<22}{} (offset 0x{:X})",
+    "signature",
+    if signature.is_present() { "present" } else { "absent" },
+    signature.offset()
+  );
+  println!(
+    "{:<22}{}",
+    "sha1",
+    signature.sha1().iter().fold(String::new(), |mut output, byte| {
+      let _ = write!(output, "{byte:02x}");
+
+      output
+    })
+  );
+
+  if let Some(strip) = strip {
+    std::fs::write(&strip, signature.stripped()).unwrap();
+    println!("stripped signature to {strip}");
+  }
+
+  if let Some(zero) = zero {
+    std::fs::write(&zero, signature.zeroed()).unwrap();
+    println!("zeroed signature to {zero}");
+  }
+
+  if let Some(key) = key {
+    let verified = signature::load_public_key(&key).map_or_else(
+      |error| {
+        println!("{error}");
+        exit(1);
+      },
+      |key| signature.verify(&key),
+    );
+
+    println!("{:<22}{}", "verified", if verified { "yes" } else { "no" });
+
+    // Let scripts flag tampered files by the exit status alone
+    if !verified {
+      exit(2);
+    }
+  }
 }
 
 fn main() {
@@ -49,64 +717,143 @@ fn main() {
     .homepage(env!("CARGO_PKG_HOMEPAGE"))
   );
 
-  let args = std::env::args().collect::<Vec<_>>();
+  let mut args = std::env::args().collect::<Vec<_>>();
+  let options = match Options::take_from(&mut args) {
+    Ok(options) => options,
+    Err(error) => {
+      println!("{error}");
+      exit(1);
+    }
+  };
+
+  if args.get(1).is_some_and(|command| command == "info") {
+    let json = take_flag(&mut args, "--json");
+
+    if let Some(path) = args.get(2) {
+      info(path, json);
+
+      return;
+    }
+  }
+
+  if let (Some("signature"), Some(path)) =
+    (args.get(1).map(String::as_str), args.get(2).cloned())
+  {
+    signature(&path, &mut args);
+
+    return;
+  }
+
+  if let (Some("lineage"), Some(directory), Some(out_path)) =
+    (args.get(1).map(String::as_str), args.get(2), args.get(3))
+  {
+    lineage(directory, out_path);
+
+    return;
+  }
+
+  match (args.get(1).map(String::as_str), args.get(2), args.get(3)) {
+    (Some("index"), Some(directory), Some(out_path)) => {
+      index(directory, out_path);
+
+      return;
+    }
+    (Some("search"), Some(_), _) => {
+      search(&mut args);
+
+      return;
+    }
+    (Some("dedupe"), Some(_), _) => {
+      dedupe(&mut args);
+
+      return;
+    }
+    (Some("check"), Some(_), _) => {
+      check(&mut args);
+
+      return;
+    }
+    (Some("diff"), Some(_), Some(_)) => {
+      diff(&mut args);
+
+      return;
+    }
+    _ => {}
+  }
 
   if args.len() < 4 {
-    println!(
-      "{}, version {}(1)-{}-({})-{}\n\
-      usage:  {} <in> <index option> <out>\n\
-      index options:\n\
-             \tgif\n\
-             \tthumb\n\
-             \tdump\n\
-             \tinteger(u16)\n\n\
-             {0} home page: <https://github.com/Usugata/{0}>",
-      env!("CARGO_PKG_NAME"),
-      env!("CARGO_PKG_VERSION"),
-      env!("PROFILE"),
-      env!("TARGET"),
-      env!("GIT_COMMIT_HASH"),
-      args[0],
-    );
+    print_usage(&args[0]);
     exit(1);
   }
 
   let path = &args[1];
   let index = &args[2];
   let out_path = &args[3];
-  let mut parser = PPMParser::new_from_file(path);
-  parser.load();
+
+  if ppm::is_tmb(path) {
+    convert_tmb(path, index, out_path, &options);
+  } else if npf::is_hatena_image(path) {
+    convert_hatena_image(path, index, out_path, &options);
+  } else if ugo::is_ugo(path) {
+    convert_ugo(path, index, out_path);
+  } else {
+    convert(path, index, out_path, &options);
+  }
+
+  println!("converted {path}({index}) to {out_path}");
+}
+
+fn convert(path: &str, index: &str, out_path: &str, options: &Options) {
+  let mut parser = options.recovery.map_or_else(
+    || open_or_exit(path),
+    |recovery| open_recovering(path, recovery),
+  );
   let frame_count = usize::from(parser.get_frame_count());
 
-  match index.as_str() {
-    "gif" => {
-      #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
-      let frame_delay = ((1.0 / parser.get_framerate()) * 100.0) as u16;
-      let frames = (0..parser.get_frame_count())
-        .map(|i| get_image(&mut parser, i as usize))
-        .collect::<Vec<DynamicImage>>();
-      let mut file_out = std::fs::File::create(out_path).unwrap();
-      let mut gif_encoder = image::codecs::gif::GifEncoder::new(&mut file_out);
-
-      for frame in frames {
-        let rgba_frame = frame.into_rgba8();
-        let gif_frame = image::Frame::from_parts(
-          rgba_frame,
-          0,
-          0,
-          image::Delay::from_numer_denom_ms(u32::from(frame_delay) * 10, 1),
-        );
+  match index {
+    "gif" | "apng" => {
+      let out_paths = output_paths(out_path, parser.layer_count(), options);
+      let mut outputs =
+        out_paths.iter().map(|_| Vec::new()).collect::<Vec<Vec<_>>>();
+
+      let palette = options.palette.unwrap_or_else(|| parser.default_palette());
 
-        gif_encoder.encode_frame(gif_frame).unwrap();
+      for frames in parser.frames(palette, options.layers) {
+        for (output, frame) in outputs.iter_mut().zip(frames) {
+          output.push(IndexedFrame {
+            pixels: options.scaling.apply(frame.pixels),
+            ..frame
+          });
+        }
       }
 
-      gif_encoder.set_repeat(image::codecs::gif::Repeat::Infinite).unwrap();
+      for (out_path, frames) in out_paths.iter().zip(outputs) {
+        if index == "gif" {
+          #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+          let frame_delay = ((1.0 / parser.get_framerate()) * 100.0) as u16;
+
+          export::write_gif(
+            out_path,
+            &frames,
+            frame_delay,
+            options.transparent,
+          );
+        } else {
+          export::write_apng(
+            out_path,
+            &frames,
+            parser.get_framerate(),
+            options.transparent,
+          );
+        }
+      }
     }
     "thumb" => {
       let thumb_index = parser.get_thumb_index() as usize;
-      get_image(&mut parser, thumb_index).save(out_path).unwrap();
+
+      save_frames(parser.as_mut(), thumb_index, out_path, options);
     }
-    "dump" => parser.dump_to_json(out_path),
+    "dump" => parser.dump_to_json(out_path, options.extended),
     _ => {
       if !(0..frame_count).contains(&index.parse::<usize>().unwrap()) {
         println!(
@@ -118,11 +865,12 @@ fn main() {
         exit(1);
       }
 
-      get_image(&mut parser, index.parse::<usize>().unwrap())
-        .save(out_path)
-        .unwrap();
+      save_frames(
+        parser.as_mut(),
+        index.parse::<usize>().unwrap(),
+        out_path,
+        options,
+      );
     }
   }
-
-  println!("converted {path}({index}) to {out_path}");
 }
@@ -0,0 +1,168 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use {
+  crate::{export::IndexedFrame, flipnote::read_magic},
+  byteorder::{LittleEndian, ReadBytesExt},
+  std::{
+    io::{Cursor, Read},
+    path::Path,
+  },
+};
+
+/// Whether a file is an `.npf` or `.nbf` image
+///
+/// Images share their `UGAR` magic with `.ugo` menus, so they're told apart
+/// by their extension.
+pub fn is_hatena_image(path: &str) -> bool {
+  read_magic(path).is_some_and(|magic| &magic == b"UGAR")
+    && Path::new(path).extension().is_some_and(|extension| {
+      extension.eq_ignore_ascii_case("npf")
+        || extension.eq_ignore_ascii_case("nbf")
+    })
+}
+
+/// Read `n` bytes, checking that the image holds them before allocating
+#[allow(clippy::cast_possible_truncation)]
+fn read_n_to_vec(
+  stream: &mut Cursor<Vec<u8>>,
+  n: usize,
+  what: &str,
+) -> Result<Vec<u8>, String> {
+  let remaining =
+    stream.get_ref().len().saturating_sub(stream.position() as usize);
+
+  if n > remaining {
+    return Err(format!(
+      "truncated {what}: expected {n} bytes, found {remaining}"
+    ));
+  }
+
+  let mut buffer = vec![0; n];
+
+  stream.read_exact(&mut buffer).map_err(|error| error.to_string())?;
+
+  Ok(buffer)
+}
+
+/// Expand a 5-bit colour channel to 8 bits
+#[allow(clippy::cast_possible_truncation)]
+const fn expand_channel(value: u16) -> u8 {
+  let value = (value & 0x1F) as u8;
+
+  (value << 3) | (value >> 2)
+}
+
+/// Expand a 15-bit BGR colour, as the console stores them, to 24-bit RGB
+const fn rgb555_to_rgb(colour: u16) -> (u8, u8, u8) {
+  (
+    expand_channel(colour),
+    expand_channel(colour >> 5),
+    expand_channel(colour >> 10),
+  )
+}
+
+/// Decoder for Flipnote Hatena's `.npf` and `.nbf` images
+///
+/// Both are stored as a 15-bit RGB palette followed by palette indices; four
+/// bits per pixel for `.npf`, whose first colour is transparent, and eight
+/// for `.nbf`. Neither stores its dimensions.
+///
+/// <https://github.com/pbsds/hatena-server/wiki/NPF-format>
+#[derive(Default)]
+pub struct NPFParser {
+  stream:         Cursor<Vec<u8>>,
+  bits_per_pixel: u8,
+  colours:        Vec<(u8, u8, u8)>,
+  pixels:         Vec<u8>,
+}
+impl NPFParser {
+  #[allow(unused)]
+  pub fn new(stream: Vec<u8>, bits_per_pixel: u8) -> Self {
+    Self { stream: Cursor::new(stream), bits_per_pixel, ..Self::default() }
+  }
+
+  /// `.nbf` files are read as 8-bit, and anything else as 4-bit
+  pub fn new_from_file(file: &str) -> Self {
+    let is_nbf = Path::new(file)
+      .extension()
+      .is_some_and(|extension| extension.eq_ignore_ascii_case("nbf"));
+
+    Self {
+      stream: Cursor::new(std::fs::read(file).unwrap()),
+      bits_per_pixel: if is_nbf { 8 } else { 4 },
+      ..Self::default()
+    }
+  }
+
+  /// Read the palette and pixels, failing if the image is cut short
+  pub fn load(&mut self) -> Result<(), String> {
+    // Skip the magic and section count
+    self.stream.set_position(8);
+
+    let mut read_size = || {
+      self
+        .stream
+        .read_u32::<LittleEndian>()
+        .map_err(|_| "truncated section table".to_string())
+    };
+    let palette_size = read_size()?;
+    let image_size = read_size()?;
+
+    self.colours =
+      read_n_to_vec(&mut self.stream, palette_size as usize, "palette")?
+        .chunks_exact(2)
+        .map(|pair| rgb555_to_rgb(u16::from_le_bytes([pair[0], pair[1]])))
+        .collect();
+
+    let data =
+      read_n_to_vec(&mut self.stream, image_size as usize, "image data")?;
+
+    self.pixels = if self.bits_per_pixel == 4 {
+      // Two pixels per byte, the leftmost in the low nibble
+      data.iter().flat_map(|byte| [byte & 0x0F, byte >> 4]).collect()
+    } else {
+      data
+    };
+
+    Ok(())
+  }
+
+  /// Only `.npf` images have a transparent colour
+  pub const fn is_transparent(&self) -> bool { self.bits_per_pixel == 4 }
+
+  /// Guess an image's dimensions from its pixel count; icons are square,
+  /// and anything else is assumed to span the width of the screen
+  const fn guess_width(&self) -> usize {
+    let pixel_count = self.pixels.len();
+    let side = pixel_count.isqrt();
+
+    if side * side == pixel_count {
+      side
+    } else if pixel_count % 256 == 0 {
+      256
+    } else {
+      pixel_count
+    }
+  }
+
+  /// Lay the image out in rows of `width` pixels, or of a width guessed from
+  /// the pixel count
+  pub fn get_frame(&self, width: Option<usize>) -> IndexedFrame {
+    let width = width.unwrap_or_else(|| self.guess_width()).max(1);
+    let mut colours = self.colours.clone();
+    let colour_count =
+      usize::from(self.pixels.iter().copied().max().unwrap_or(0)) + 1;
+
+    // Pad the palette so that indices beyond it are drawn with its last
+    // colour rather than panicking
+    if colours.len() < colour_count {
+      colours.resize(colour_count, colours.last().copied().unwrap_or_default());
+    }
+
+    IndexedFrame {
+      pixels: self.pixels.chunks_exact(width).map(<[u8]>::to_vec).collect(),
+      colours,
+    }
+  }
+}
@@ -0,0 +1,157 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use std::path::Path;
+
+/// The colours a frame can be drawn with
+///
+/// A PPM frame's paper is either black or white, and each layer's pen is
+/// either the inverse of the paper, red, or blue. KWZ frames can additionally
+/// use yellow and green for both paper and pens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+  pub black:  (u8, u8, u8),
+  pub white:  (u8, u8, u8),
+  pub red:    (u8, u8, u8),
+  pub blue:   (u8, u8, u8),
+  pub yellow: (u8, u8, u8),
+  pub green:  (u8, u8, u8),
+}
+impl Palette {
+  /// Colours as Flipnote Studio draws them on the console
+  pub const DSI: Self = Self {
+    black:  (0x0E, 0x0E, 0x0E),
+    white:  (0xFF, 0xFF, 0xFF),
+    red:    (0xFF, 0x2A, 0x2A),
+    blue:   (0x0A, 0x39, 0xFF),
+    yellow: (0xFF, 0xE7, 0x00),
+    green:  (0x00, 0x86, 0x31),
+  };
+  /// Greyscale, keeping red and blue distinguishable by their luminance
+  pub const GREYSCALE: Self = Self {
+    black:  (0x0E, 0x0E, 0x0E),
+    white:  (0xFF, 0xFF, 0xFF),
+    red:    (0x69, 0x69, 0x69),
+    blue:   (0x41, 0x41, 0x41),
+    yellow: (0xD8, 0xD8, 0xD8),
+    green:  (0x5B, 0x5B, 0x5B),
+  };
+  /// Colours as the Flipnote Hatena web player drew them
+  pub const HATENA: Self = Self {
+    black:  (0x00, 0x00, 0x00),
+    white:  (0xFF, 0xFF, 0xFF),
+    red:    (0xFF, 0x00, 0x00),
+    blue:   (0x00, 0x00, 0xFF),
+    yellow: (0xFF, 0xFF, 0x00),
+    green:  (0x00, 0x80, 0x00),
+  };
+  /// Pure black and white with colour-blind-safe pens
+  ///
+  /// <https://jfly.uni-koeln.de/color/>
+  pub const HIGH_CONTRAST: Self = Self {
+    black:  (0x00, 0x00, 0x00),
+    white:  (0xFF, 0xFF, 0xFF),
+    red:    (0xD5, 0x5E, 0x00),
+    blue:   (0x00, 0x72, 0xB2),
+    yellow: (0xF0, 0xE4, 0x42),
+    green:  (0x00, 0x9E, 0x73),
+  };
+  /// Colours as Flipnote Studio 3D draws them on the console
+  pub const N3DS: Self = Self {
+    black:  (0x10, 0x10, 0x10),
+    white:  (0xFF, 0xFF, 0xFF),
+    red:    (0xFF, 0x10, 0x10),
+    blue:   (0x00, 0x38, 0xCE),
+    yellow: (0xFF, 0xE7, 0x00),
+    green:  (0x00, 0x86, 0x31),
+  };
+  pub const PRESETS: [(&'static str, Self); 5] = [
+    ("dsi", Self::DSI),
+    ("3ds", Self::N3DS),
+    ("hatena", Self::HATENA),
+    ("high-contrast", Self::HIGH_CONTRAST),
+    ("greyscale", Self::GREYSCALE),
+  ];
+
+  pub fn from_preset(name: &str) -> Option<Self> {
+    Self::PRESETS
+      .iter()
+      .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+      .map(|(_, palette)| *palette)
+  }
+
+  /// Parse four hexadecimal colours in the order black, white, red, blue,
+  /// optionally followed by yellow and green
+  ///
+  /// Colours may be separated by commas or whitespace, and may be prefixed
+  /// with `#`; e.g., `#000000,#FFFFFF,#FF0000,#0000FF`. Yellow and green
+  /// default to those of the `dsi` preset.
+  pub fn from_hex(values: &str) -> Result<Self, String> {
+    let colours = values
+      .split(|c: char| c == ',' || c.is_whitespace())
+      .filter(|value| !value.is_empty())
+      .map(parse_hex_colour)
+      .collect::<Result<Vec<_>, _>>()?;
+
+    match *colours.as_slice() {
+      [black, white, red, blue] =>
+        Ok(Self { black, white, red, blue, ..Self::DSI }),
+      [black, white, red, blue, yellow, green] =>
+        Ok(Self { black, white, red, blue, yellow, green }),
+      _ => Err(format!(
+        "invalid palette({values}), expected four colours: black, white, red, \
+         blue, optionally followed by yellow and green"
+      )),
+    }
+  }
+
+  /// Load a palette file containing the same four colours accepted by
+  /// [`Self::from_hex`]
+  pub fn from_file(path: &Path) -> Result<Self, String> {
+    Self::from_hex(
+      &std::fs::read_to_string(path).map_err(|error| {
+        format!("could not read {}: {error}", path.display())
+      })?,
+    )
+  }
+
+  /// Resolve a `--palette` value as a preset name, a palette file, or a list
+  /// of hexadecimal colours, in that order
+  pub fn parse(value: &str) -> Result<Self, String> {
+    if let Some(palette) = Self::from_preset(value) {
+      return Ok(palette);
+    }
+
+    let path = Path::new(value);
+
+    if path.is_file() {
+      Self::from_file(path)
+    } else {
+      Self::from_hex(value).map_err(|error| {
+        format!(
+          "{error}\navailable palette presets: {}",
+          Self::PRESETS.map(|(name, _)| name).join(", ")
+        )
+      })
+    }
+  }
+}
+impl Default for Palette {
+  fn default() -> Self { Self::DSI }
+}
+
+fn parse_hex_colour(value: &str) -> Result<(u8, u8, u8), String> {
+  let hex = value.trim_start_matches('#');
+  let channel = |range: std::ops::Range<usize>| {
+    hex
+      .get(range)
+      .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+      .ok_or_else(|| format!("invalid colour({value})"))
+  };
+
+  if hex.len() != 6 {
+    return Err(format!("invalid colour({value}), expected RRGGBB"));
+  }
+
+  Ok((channel(0..2)?, channel(2..4)?, channel(4..6)?))
+}
@@ -4,10 +4,19 @@
 #![allow(clippy::cast_sign_loss)]
 
 use {
+  crate::{
+    adpcm, check,
+    export::IndexedFrame,
+    filename::{FilenameFragment, PpmFilename},
+    flipnote::{Flipnote, Metadata, SoundTrack, read_magic, write_authorship},
+    fsid::FlipnoteStudioId,
+    palette::Palette,
+    signature::Signature,
+  },
   byteorder::{LittleEndian, ReadBytesExt},
   chrono::{DateTime, NaiveDateTime, TimeZone, Utc},
   std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::Write,
     fs,
     io::{Cursor, Read},
@@ -17,7 +26,7 @@ use {
 };
 
 /// Flipnote speed -> frames per second
-fn framerates() -> &'static HashMap<u8, f64> {
+pub fn framerates() -> &'static HashMap<u8, f64> {
   static FRAMERATES: OnceLock<HashMap<u8, f64>> = OnceLock::new();
 
   FRAMERATES.get_or_init(|| {
@@ -37,67 +46,151 @@ fn framerates() -> &'static HashMap<u8, f64> {
 }
 
 /// Thumbnail bitmap RGB colours
-// const THUMBNAIL_PALETTE: &'static [(u64, u64, u64)] = &[
-//   (0xFF, 0xFF, 0xFF),
-//   (0x52, 0x52, 0x52),
-//   (0xFF, 0xFF, 0xFF),
-//   (0x9C, 0x9C, 0x9C),
-//   (0xFF, 0x48, 0x44),
-//   (0xC8, 0x51, 0x4F),
-//   (0xFF, 0xAD, 0xAC),
-//   (0x00, 0xFF, 0x00),
-//   (0x48, 0x40, 0xFF),
-//   (0x51, 0x4F, 0xB8),
-//   (0xAD, 0xAB, 0xFF),
-//   (0x00, 0xFF, 0x00),
-//   (0xB6, 0x57, 0xB7),
-//   (0x00, 0xFF, 0x00),
-//   (0x00, 0xFF, 0x00),
-//   (0x00, 0xFF, 0x00),
-// ];
-// Frame RGB colours
-const BLACK: (u8, u8, u8) = (0x0E, 0x0E, 0x0E);
-const WHITE: (u8, u8, u8) = (0xFF, 0xFF, 0xFF);
-const BLUE: (u8, u8, u8) = (0x0A, 0x39, 0xFF);
-const RED: (u8, u8, u8) = (0xFF, 0x2A, 0x2A);
+const THUMBNAIL_PALETTE: [(u8, u8, u8); 16] = [
+  (0xFF, 0xFF, 0xFF),
+  (0x52, 0x52, 0x52),
+  (0xFF, 0xFF, 0xFF),
+  (0x9C, 0x9C, 0x9C),
+  (0xFF, 0x48, 0x44),
+  (0xC8, 0x51, 0x4F),
+  (0xFF, 0xAD, 0xAC),
+  (0x00, 0xFF, 0x00),
+  (0x48, 0x40, 0xFF),
+  (0x51, 0x4F, 0xB8),
+  (0xAD, 0xAB, 0xFF),
+  (0x00, 0xFF, 0x00),
+  (0xB6, 0x57, 0xB7),
+  (0x00, 0xFF, 0x00),
+  (0x00, 0xFF, 0x00),
+  (0x00, 0xFF, 0x00),
+];
+
+/// Flipnote Hatena's `.tmb` files are the first 0x6A0 bytes of a PPM; the
+/// header, metadata, and thumbnail
+pub const TMB_SIZE: u64 = 0x06A0;
 
 macro read_n_to_as_utf8_from_stream($n:expr, $from:ident) {
-  String::from_utf8({
-    let mut buffer = vec![0; $n];
-
-    $from.stream.read_exact(&mut buffer).unwrap();
-
-    buffer
-  })
-  .unwrap()
+  String::from_utf8_lossy(&read_n_to_vec(&mut $from.stream, $n)).into_owned()
 }
 
 macro read_n_of_size_from_to_vec($n:expr, $from:tt, $size:ty) {{
   let mut buffer = vec![0 as $size; $n];
 
-  $from.stream.read_exact(&mut buffer).unwrap();
+  let _ = $from.stream.read(&mut buffer);
 
   buffer
 }}
 
+/// Where the sound header starts; after the animation data and a sound
+/// effect flag for each frame, padded to a multiple of four
+///
+/// <https://github.com/pbsds/hatena-server/wiki/PPM-format#sound-data-section>
+pub const fn sound_header_offset(
+  animation_data_size: u64,
+  frame_count: u64,
+) -> u64 {
+  (0x06A0 + animation_data_size + frame_count).next_multiple_of(4)
+}
+
+/// Whether a file holds only the header, metadata, and thumbnail of a PPM,
+/// as `.tmb` files do
+pub fn is_tmb(path: &str) -> bool {
+  fs::metadata(path).is_ok_and(|metadata| metadata.len() <= TMB_SIZE)
+    && read_magic(path).is_some_and(|magic| &magic == b"PARA")
+}
+
+/// [`is_tmb`] for a file already read into memory
+pub fn is_tmb_bytes(bytes: &[u8]) -> bool {
+  bytes.len() as u64 <= TMB_SIZE && bytes.starts_with(b"PARA")
+}
+
 fn strip_null(string: &str) -> String { string.replace(char::from(0), "") }
 
+/// Read `n` bytes, of which any past the end of the stream read as zeroes
 fn read_n_to_vec(stream: &mut Cursor<Vec<u8>>, n: usize) -> Vec<u8> {
   let mut buffer = vec![0; n];
 
-  stream.read_exact(&mut buffer).unwrap();
+  // A cursor reads everything it holds, up to the length of the buffer
+  let _ = stream.read(&mut buffer);
 
   buffer
 }
 
-fn vec_u8_to_string(vec: &[u8]) -> String {
-  vec.iter().rev().fold(String::new(), |mut output, m| {
-    let _ = write!(output, "{m:02X}");
+/// Per-frame header details, read without decoding the frame's pixels
+///
+/// <https://github.com/pbsds/hatena-server/wiki/PPM-format#frame-data>
+pub struct FrameInfo {
+  pub offset:            u32,
+  /// Number of bytes the frame's header and line data occupy
+  pub size:              u32,
+  pub is_new_frame:      bool,
+  pub translation_flags: u8,
+  pub translation_x:     i8,
+  pub translation_y:     i8,
+  /// `1` for white paper, `0` for black
+  pub paper_colour:      u8,
+  /// Pen of each layer; `1` is the inverse of the paper, `2` is red, and `3`
+  /// is blue
+  pub pens:              [u8; 2],
+  /// Number of lines of each line type (0-3) in each layer
+  pub line_types:        [[u16; 4]; 2],
+  /// Sound effects one, two, and three, packed into the lowest three bits
+  pub sound_effects:     u8,
+}
+impl FrameInfo {
+  const fn pen_name(&self, pen: u8) -> Option<&'static str> {
+    match pen {
+      1 if self.paper_colour == 1 => Some("black"),
+      1 => Some("white"),
+      2 => Some("red"),
+      3 => Some("blue"),
+      _ => None,
+    }
+  }
+
+  pub fn to_json(&self, index: usize) -> serde_json::Value {
+    serde_json::json!({
+      "index": index,
+      "offset": self.offset,
+      "size": self.size,
+      "new_frame": self.is_new_frame,
+      "translation_flags": self.translation_flags,
+      "translation_x": self.translation_x,
+      "translation_y": self.translation_y,
+      "paper_colour": if self.paper_colour == 1 { "white" } else { "black" },
+      "layer_1_pen": self.pen_name(self.pens[0]),
+      "layer_2_pen": self.pen_name(self.pens[1]),
+      "layer_1_line_types": self.line_types[0],
+      "layer_2_line_types": self.line_types[1],
+      "se1": self.sound_effects & 0x1 != 0,
+      "se2": self.sound_effects >> 1 & 0x1 != 0,
+      "se3": self.sound_effects >> 2 & 0x1 != 0,
+    })
+  }
+}
 
-    output
-  })
+/// What a damaged frame is decoded as in recovery mode
+#[derive(Clone, Copy)]
+pub enum Recovery {
+  Blank,
+  /// The last frame before it
+  Repeat,
+}
+impl Recovery {
+  pub fn parse(recovery: &str) -> Result<Self, String> {
+    match recovery {
+      "blank" => Ok(Self::Blank),
+      "repeat" => Ok(Self::Repeat),
+      _ => Err(format!(
+        "invalid recovery mode({recovery}), expected blank or repeat"
+      )),
+    }
+  }
 }
 
+/// Header of a frame which can't be read; white paper with black ink
+const FALLBACK_FRAME_HEADER: u64 = 0b0000_1011;
+
 pub struct PPMParser {
   stream:              Cursor<Vec<u8>>,
   layers:              Vec<Vec<Vec<u8>>>,
@@ -111,12 +204,12 @@ pub struct PPMParser {
   root_author_name:    String,
   parent_author_name:  String,
   current_author_name: String,
-  parent_author_id:    String,
-  current_author_id:   String,
-  parent_filename:     String,
-  current_filename:    String,
-  root_author_id:      String,
-  partial_filename:    String,
+  parent_author_id:    FlipnoteStudioId,
+  current_author_id:   FlipnoteStudioId,
+  parent_filename:     PpmFilename,
+  current_filename:    PpmFilename,
+  root_author_id:      FlipnoteStudioId,
+  partial_filename:    FilenameFragment,
   timestamp:           DateTime<Utc>,
   layer_1_visible:     bool,
   layer_2_visible:     bool,
@@ -126,9 +219,13 @@ pub struct PPMParser {
   framerate:           f64,
   bgm_framerate:       f64,
   offset_table:        Vec<u32>,
+  table_size:          u16,
+  sound_sizes:         [u32; 4],
+  recovery:            Option<Recovery>,
+  /// Frames which can't be decoded, by index, and why
+  damaged_frames:      BTreeMap<usize, String>,
 }
 impl PPMParser {
-  #[allow(unused)]
   pub fn new(stream: Vec<u8>) -> Self {
     Self { stream: Cursor::new(stream), ..Self::default() }
   }
@@ -141,8 +238,11 @@ impl PPMParser {
   }
 
   pub fn load(&mut self) {
-    self.read_header();
-    self.read_meta();
+    self.load_metadata();
+    self.load_animation();
+  }
+
+  fn load_animation(&mut self) {
     self.read_animation_header();
     self.read_sound_header();
     self.layers = vec![vec![vec![0; 256]; 192]; 2];
@@ -150,6 +250,61 @@ impl PPMParser {
     self.prev_frame_index = isize::MAX as usize; // -1
   }
 
+  /// Decode as much of a damaged file as possible; frames which can't be
+  /// decoded are substituted, returning which frames were and why
+  ///
+  /// Truncated headers read as zeroes, as they do when loading normally.
+  pub fn load_recovering(
+    &mut self,
+    recovery: Recovery,
+  ) -> &BTreeMap<usize, String> {
+    self.damaged_frames = check::damaged_frames(self.stream.get_ref());
+    self.recovery = Some(recovery);
+
+    self.load();
+
+    &self.damaged_frames
+  }
+
+  /// Whether a frame is substituted rather than decoded
+  fn is_damaged(&self, index: usize) -> bool {
+    self.recovery.is_some() && self.damaged_frames.contains_key(&index)
+  }
+
+  /// The header of a frame, or of the frame it's substituted with
+  fn read_frame_header(&mut self, index: usize) -> u64 {
+    let Some(offset) = (0..=index)
+      .rev()
+      .find(|&index| !self.is_damaged(index))
+      .and_then(|index| self.offset_table.get(index).copied())
+    else {
+      return FALLBACK_FRAME_HEADER;
+    };
+
+    self.stream.set_position(u64::from(offset));
+
+    self.stream.read_uint::<LittleEndian>(1).unwrap_or_default()
+  }
+
+  /// Stand in for a frame which can't be decoded with a blank frame, or with
+  /// the frame before it, which has already been decoded
+  fn substitute_frame(&mut self, index: usize, recovery: Recovery) {
+    self.prev_layers.clone_from_slice(&self.layers);
+    self.prev_frame_index = index;
+
+    // The first frame has no frame before it to repeat
+    if matches!(recovery, Recovery::Blank) || index == 0 {
+      self.layers.fill(vec![vec![0u8; 256]; 192]);
+    }
+  }
+
+  /// Decode only the header and metadata, which is all a `.tmb` holds, so
+  /// that both `.tmb` files and full PPMs can be indexed cheaply
+  pub fn load_metadata(&mut self) {
+    self.read_header();
+    self.read_meta();
+  }
+
   /// Decode header
   ///
   /// <https://github.com/pbsds/hatena-server/wiki/PPM-format#file-header>
@@ -157,45 +312,35 @@ impl PPMParser {
     self.stream.set_position(0);
 
     let _magic = read_n_to_as_utf8_from_stream!(4, self);
-    let animation_data_size = self.stream.read_u32::<LittleEndian>().unwrap();
-    let sound_data_size = self.stream.read_u32::<LittleEndian>().unwrap();
-    let frame_count = self.stream.read_u16::<LittleEndian>().unwrap();
-    let _version = self.stream.read_u16::<LittleEndian>().unwrap();
+    let animation_data_size =
+      self.stream.read_u32::<LittleEndian>().unwrap_or_default();
+    let sound_data_size =
+      self.stream.read_u32::<LittleEndian>().unwrap_or_default();
+    let frame_count =
+      self.stream.read_u16::<LittleEndian>().unwrap_or_default();
+    let _version = self.stream.read_u16::<LittleEndian>().unwrap_or_default();
 
     self.animation_data_size = animation_data_size;
     self.sound_data_size = sound_data_size;
-    self.frame_count = frame_count + 1;
+    // Saturates for the 65536 frames the header can describe, but a `u16`
+    // can't count
+    self.frame_count = frame_count.saturating_add(1);
   }
 
-  fn read_filename(&mut self) -> String {
-    // Parent and current filenames are stored as:
-    //
-    // - three bytes representing the last six digits of the console's MAC
-    //   address
-    // - thirteen-character `String`
-    // - `u16` edit counter
-    let mac = read_n_to_vec(&mut self.stream, 3);
-    let ident = read_n_to_vec(&mut self.stream, 13)
-      .into_iter()
-      .map(|c| c as char)
-      .collect::<String>();
-    let edits = self.stream.read_u16::<LittleEndian>().unwrap();
+  fn read_author_id(&mut self) -> FlipnoteStudioId {
+    let mut bytes = [0; 8];
 
-    // Filenames are formatted as
-    // <three-byte MAC as hexadecimal>_<thirteen-character string>_<edit counter
-    // as three-digit number>
-    //
-    // Example: F78DA8_14768882B56B8_030
-    format!(
-      "{}_{}_{:#03}",
-      mac.iter().fold(String::new(), |mut output, m| {
-        let _ = write!(output, "{m:02X}");
-
-        output
-      }),
-      String::from_utf8(ident.as_bytes().to_vec()).unwrap(),
-      edits,
-    )
+    let _ = self.stream.read(&mut bytes);
+
+    FlipnoteStudioId::from_stored(bytes)
+  }
+
+  fn read_filename(&mut self) -> PpmFilename {
+    let mut bytes = [0; 18];
+
+    let _ = self.stream.read(&mut bytes);
+
+    PpmFilename::from_stored(&bytes)
   }
 
   /// Decode metadata
@@ -204,27 +349,28 @@ impl PPMParser {
   fn read_meta(&mut self) {
     self.stream.set_position(0x10);
 
-    self.lock = self.stream.read_u16::<LittleEndian>().unwrap();
-    self.thumb_index = self.stream.read_u16::<LittleEndian>().unwrap();
+    self.lock = self.stream.read_u16::<LittleEndian>().unwrap_or_default();
+    self.thumb_index =
+      self.stream.read_u16::<LittleEndian>().unwrap_or_default();
     self.root_author_name =
       strip_null(&read_n_to_as_utf8_from_stream!(22, self));
     self.parent_author_name =
       strip_null(&read_n_to_as_utf8_from_stream!(22, self));
     self.current_author_name =
       strip_null(&read_n_to_as_utf8_from_stream!(22, self));
-    self.parent_author_id =
-      vec_u8_to_string(read_n_to_vec(&mut self.stream, 8).as_mut_slice());
-    self.current_author_id =
-      vec_u8_to_string(read_n_to_vec(&mut self.stream, 8).as_mut_slice());
+    self.parent_author_id = self.read_author_id();
+    self.current_author_id = self.read_author_id();
     self.parent_filename = self.read_filename();
     self.current_filename = self.read_filename();
-    self.root_author_id =
-      vec_u8_to_string(read_n_to_vec(&mut self.stream, 8).as_mut_slice());
-    self.partial_filename =
-      vec_u8_to_string(read_n_to_vec(&mut self.stream, 8).as_slice()); // Not really useful for anything
+    self.root_author_id = self.read_author_id();
+    // The start of the root Flipnote's filename
+    let mut partial_filename = [0; 8];
+
+    let _ = self.stream.read(&mut partial_filename);
+    self.partial_filename = FilenameFragment::from_stored(partial_filename);
 
     // Timestamp is stored as the number of seconds since 2000, January, 1st
-    let timestamp = self.stream.read_u32::<LittleEndian>().unwrap();
+    let timestamp = self.stream.read_u32::<LittleEndian>().unwrap_or_default();
     self.timestamp = TimeZone::from_utc_datetime(
       // We add 946684800 to convert this to a more common Unix timestamp,
       // which starts on 1970, January, 1st
@@ -234,8 +380,8 @@ impl PPMParser {
     );
   }
 
-  #[allow(unused)]
-  fn read_thumbnail(&mut self) -> Vec<Vec<u64>> {
+  /// Decode the 64x48 thumbnail into indices of its fixed 16-colour palette
+  pub fn read_thumbnail(&mut self) -> Vec<Vec<u8>> {
     self.stream.set_position(0xA0);
 
     let mut bitmap = vec![vec![0; 64]; 48];
@@ -248,7 +394,7 @@ impl PPMParser {
         // [This](https://linuxtut.com/en/ff1ac20b39137f1ccdb9/) can be used,
         // but let's do it in Rust.
         for pixel in (0..8).step_by(2) {
-          let byte = self.stream.read_uint::<LittleEndian>(1).unwrap();
+          let byte = self.stream.read_u8().unwrap_or_default();
           let x = tile_x + pixel;
           let y = tile_y + line;
 
@@ -261,12 +407,114 @@ impl PPMParser {
     bitmap
   }
 
+  pub fn get_thumbnail(&mut self) -> IndexedFrame {
+    IndexedFrame {
+      pixels:  self.read_thumbnail(),
+      colours: THUMBNAIL_PALETTE.to_vec(),
+    }
+  }
+
+  /// The fields of the header and metadata, which `.tmb` files also hold
+  pub fn header_json(&self) -> serde_json::Value {
+    serde_json::json!({
+      "animation_data_size": self.animation_data_size,
+      "sound_data_size": self.sound_data_size,
+      "frame_count": self.frame_count,
+      "lock": self.lock,
+      "thumb_index": self.thumb_index,
+      "root_author_name": self.root_author_name,
+      "parent_author_name": self.parent_author_name,
+      "current_author_name": self.current_author_name,
+      "root_author_id": self.root_author_id,
+      "parent_author_id": self.parent_author_id,
+      "current_author_id": self.current_author_id,
+      "parent_filename": self.parent_filename.to_string(),
+      "current_filename": self.current_filename.to_string(),
+      "partial_filename": self.partial_filename.to_string(),
+      "public_filename": self.current_filename.to_public(),
+      "filename_issues": self.filename_issues(),
+      "author_id_issues": self.author_id_issues(),
+      "timestamp": self.timestamp.to_string(),
+    })
+  }
+
+  /// Inconsistencies between the stored filenames and the authors whose
+  /// consoles they were made on, whose IDs end with the same MAC digits
+  pub fn filename_issues(&self) -> Vec<String> {
+    let mut issues = Vec::new();
+
+    for (role, filename, author_id) in [
+      ("parent", &self.parent_filename, &self.parent_author_id),
+      ("current", &self.current_filename, &self.current_author_id),
+    ] {
+      if !filename.is_well_formed() {
+        issues.push(format!("{role} filename {filename} is malformed"));
+      }
+
+      if author_id.mac_hex() != filename.mac_hex() {
+        issues.push(format!(
+          "{role} filename {filename} was not made by its author ({author_id})"
+        ));
+      }
+    }
+
+    if self.root_author_id.mac_hex() != self.partial_filename.mac_hex() {
+      issues.push(format!(
+        "root filename {} was not made by its author ({})",
+        self.partial_filename, self.root_author_id
+      ));
+    }
+
+    issues
+  }
+
+  /// Author IDs which couldn't have been issued by a console, or which are
+  /// placeholders
+  pub fn author_id_issues(&self) -> Vec<String> {
+    let mut issues = Vec::new();
+
+    for (role, author_id) in [
+      ("root", self.root_author_id),
+      ("parent", self.parent_author_id),
+      ("current", self.current_author_id),
+    ] {
+      if author_id.is_placeholder() {
+        issues.push(format!("{role} author ID {author_id} is a placeholder"));
+      } else if !author_id.is_valid() {
+        issues.push(format!("{role} author ID {author_id} is malformed"));
+      }
+    }
+
+    issues
+  }
+
+  /// Human-readable summary of the header and metadata, as shown by
+  /// `para info` for `.tmb` files
+  pub fn header_summary(&self) -> String {
+    let mut summary = String::new();
+
+    write_authorship(&mut summary, &self.metadata());
+    let _ = writeln!(
+      summary,
+      "{:<22}{} (thumbnail {})",
+      "frames", self.frame_count, self.thumb_index
+    );
+    let _ = writeln!(
+      summary,
+      "{:<22}{}",
+      "locked",
+      if self.lock == 0 { "no" } else { "yes" }
+    );
+
+    summary
+  }
+
   fn read_animation_header(&mut self) {
     self.stream.set_position(0x06A0);
 
-    let table_size = self.stream.read_u16::<LittleEndian>().unwrap();
-    let _unknown = self.stream.read_u16::<LittleEndian>().unwrap();
-    let flags = self.stream.read_u32::<LittleEndian>().unwrap();
+    let table_size = self.stream.read_u16::<LittleEndian>().unwrap_or_default();
+    let _unknown = self.stream.read_u16::<LittleEndian>().unwrap_or_default();
+    let flags = self.stream.read_u32::<LittleEndian>().unwrap_or_default();
 
     // Unpack animation flags
     self.layer_1_visible = (flags >> 11) & 0x01 != 0;
@@ -282,7 +530,10 @@ impl PPMParser {
       // should have...
       //
       // 2022. 02. 25. 03:58., Fuwn
-      for index in (0..usize::from(table_size)).step_by(4) {
+      //
+      // A table whose size isn't a multiple of four ends with a partial
+      // entry, which is ignored
+      for index in (0..usize::from(table_size) / 4 * 4).step_by(4) {
         buffer.push(
           (u32::from(from_buffer[index]))
             | (u32::from(from_buffer[index + 1]) << 8)
@@ -295,40 +546,144 @@ impl PPMParser {
     };
     self.offset_table = offset_table
       .into_iter()
-      .map(|m| m + 0x06A0 + 8 + u32::from(table_size))
+      .map(|m| m.saturating_add(0x06A0 + 8 + u32::from(table_size)))
       .collect();
+    self.table_size = table_size;
+  }
+
+  fn sound_header_offset(&self) -> u64 {
+    sound_header_offset(
+      u64::from(self.animation_data_size),
+      u64::from(self.frame_count),
+    )
   }
 
   fn read_sound_header(&mut self) {
-    // offset = frame data offset + frame data length + sound effect flags
-    //
-    // <https://github.com/pbsds/hatena-server/wiki/PPM-format#sound-data-section>
-    let mut offset =
-      0x06A0 + self.animation_data_size + u32::from(self.frame_count);
-    if offset % 2 != 0 {
-      // Account for multiple-of-four padding
-      offset += 4 - (offset % 4);
-    }
+    self.stream.set_position(self.sound_header_offset());
 
-    self.stream.set_position(u64::from(offset));
+    // BGM, followed by sound effects one, two, and three
+    for size in &mut self.sound_sizes {
+      *size = self.stream.read_u32::<LittleEndian>().unwrap_or_default();
+    }
 
-    let _bgm_size = self.stream.read_u32::<LittleEndian>().unwrap();
-    let _se1_size = self.stream.read_u32::<LittleEndian>().unwrap();
-    let _se2_size = self.stream.read_u32::<LittleEndian>().unwrap();
-    let _se3_size = self.stream.read_u32::<LittleEndian>().unwrap();
-    let frame_speed = self.stream.read_u8().unwrap();
-    let bgm_speed = self.stream.read_u8().unwrap();
+    let frame_speed = self.stream.read_u8().unwrap_or_default();
+    let bgm_speed = self.stream.read_u8().unwrap_or_default();
 
-    self.frame_speed = 8 - frame_speed;
-    self.bgm_speed = 8 - bgm_speed;
+    // Speeds out of range, as damaged files have, play at the slowest speed
+    self.frame_speed = 8u8.saturating_sub(frame_speed).max(1);
+    self.bgm_speed = 8u8.saturating_sub(bgm_speed).max(1);
     self.framerate = *framerates().get(&self.frame_speed).unwrap();
     self.bgm_framerate = *framerates().get(&self.bgm_speed).unwrap();
   }
 
+  /// The signature directly follows the sound data, which is measured by its
+  /// tracks as some files leave `sound_data_size` at zero
+  fn signature_offset(&self) -> u64 {
+    self.sound_header_offset()
+      + 0x20
+      + self.sound_sizes.iter().copied().map(u64::from).sum::<u64>()
+  }
+
+  /// The file's RSA-SHA1 signature, which covers everything before it
+  ///
+  /// <https://github.com/pbsds/hatena-server/wiki/PPM-format#signature>
+  #[allow(clippy::cast_possible_truncation)]
+  pub fn get_signature(&self) -> Signature<'_> {
+    Signature::new(self.stream.get_ref(), self.signature_offset() as usize)
+  }
+
+  /// Walk a frame's header and line data to describe it, skipping over the
+  /// pixel chunks rather than decoding them
+  pub fn get_frame_info(&mut self, index: usize) -> FrameInfo {
+    let offset = self.frame_offset(index);
+
+    self.stream.set_position(u64::from(offset));
+
+    let header = self.stream.read_u8().unwrap_or_default();
+    let translation_flags = (header >> 5) & 0x03;
+    let translation_x = if translation_flags != 0 {
+      self.stream.read_i8().unwrap_or_default()
+    } else {
+      0
+    };
+    let translation_y = if translation_flags != 0 {
+      self.stream.read_i8().unwrap_or_default()
+    } else {
+      0
+    };
+    let line_types = [
+      read_n_of_size_from_to_vec!(48, self, u8),
+      read_n_of_size_from_to_vec!(48, self, u8),
+    ];
+    let mut histogram = [[0; 4]; 2];
+
+    for (layer, line_types) in line_types.iter().enumerate() {
+      let mut generator = Self::read_line_types(line_types);
+
+      while let std::ops::CoroutineState::Yielded((_, line_type)) =
+        std::pin::Pin::new(&mut generator).resume(())
+      {
+        histogram[layer][usize::from(line_type)] += 1;
+
+        let skip = match line_type {
+          // Chunk usage, followed by one byte for each used chunk
+          1 | 2 => {
+            let chunk_usage = self
+              .stream
+              .read_u32::<byteorder::BigEndian>()
+              .unwrap_or_default();
+
+            chunk_usage.count_ones()
+          }
+          3 => 32,
+          _ => 0,
+        };
+
+        self
+          .stream
+          .set_position(self.stream.position().saturating_add(u64::from(skip)));
+      }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    let size = self.stream.position().saturating_sub(u64::from(offset)) as u32;
+
+    FrameInfo {
+      offset,
+      size,
+      is_new_frame: (header >> 7) & 0x01 != 0,
+      translation_flags,
+      translation_x,
+      translation_y,
+      paper_colour: header & 0x1,
+      pens: [(header >> 1) & 0x3, (header >> 3) & 0x3],
+      line_types: histogram,
+      sound_effects: self.read_sound_effect_flags(index),
+    }
+  }
+
+  /// Sound effect flags are stored as one byte per frame, directly after the
+  /// animation data
+  fn read_sound_effect_flags(&mut self, index: usize) -> u8 {
+    self.stream.set_position(
+      0x06A0 + u64::from(self.animation_data_size) + index as u64,
+    );
+
+    self.stream.read_u8().unwrap_or_default() & 0x07
+  }
+
+  /// Where a frame's data starts; the end of the file for frames the offset
+  /// table has no entry for, so that they read as zeroes
+  fn frame_offset(&self, index: usize) -> u32 {
+    self.offset_table.get(index).copied().unwrap_or_else(|| {
+      u32::try_from(self.stream.get_ref().len()).unwrap_or(u32::MAX)
+    })
+  }
+
   fn frame_is_new(&mut self, index: usize) -> bool {
-    self.stream.set_position(u64::from(*self.offset_table.get(index).unwrap()));
+    self.stream.set_position(u64::from(self.frame_offset(index)));
 
-    self.stream.read_uint::<LittleEndian>(1).unwrap() >> 7 & 0x1 != 0
+    self.stream.read_uint::<LittleEndian>(1).unwrap_or_default() >> 7 & 0x1 != 0
   }
 
   fn read_line_types(
@@ -344,15 +699,46 @@ impl PPMParser {
     }
   }
 
+  /// Whether a frame can be decoded without the frame before it; frames the
+  /// offset table has no entry for hold no data to be based on it
+  fn is_independent(&mut self, index: usize) -> bool {
+    match self.recovery {
+      Some(recovery) if self.damaged_frames.contains_key(&index) =>
+        matches!(recovery, Recovery::Blank),
+      _ => index >= self.offset_table.len() || self.frame_is_new(index),
+    }
+  }
+
   fn read_frame(&mut self, index: usize) -> &Vec<Vec<Vec<u8>>> {
-    // Decode the previous frames if needed
-    if index != 0
-      && self.prev_frame_index != index - 1
-      && !self.frame_is_new(index)
+    // Decode the previous frames if needed, from the last one which doesn't
+    // depend on those before it; iteratively, as a Flipnote can be thousands
+    // of frames long
+    let mut first = index;
+
+    while first != 0
+      && self.prev_frame_index != first - 1
+      && !self.is_independent(first)
     {
-      self.read_frame(index - 1);
+      first -= 1;
     }
 
+    for index in first..=index {
+      match self.recovery {
+        Some(recovery) if self.damaged_frames.contains_key(&index) =>
+          self.substitute_frame(index, recovery),
+        // Frames the offset table has no entry for have no data to decode
+        _ if index >= self.offset_table.len() =>
+          self.substitute_frame(index, Recovery::Blank),
+        _ => self.decode_frame(index),
+      }
+    }
+
+    &self.layers
+  }
+
+  /// Decode a frame onto the layer buffers, which must hold the frame before
+  /// it
+  fn decode_frame(&mut self, index: usize) {
     // Copy the current layer buffers to the previous ones
     self.prev_layers.clone_from_slice(&self.layers);
     self.prev_frame_index = index;
@@ -360,17 +746,17 @@ impl PPMParser {
     self.layers.fill(vec![vec![0u8; 256]; 192]);
 
     // Seek to the frame offset so we can start reading
-    self.stream.set_position(u64::from(*self.offset_table.get(index).unwrap()));
+    self.stream.set_position(u64::from(self.frame_offset(index)));
 
     // Unpack frame header flags
-    let header = self.stream.read_uint::<LittleEndian>(1).unwrap();
+    let header = self.stream.read_uint::<LittleEndian>(1).unwrap_or_default();
     let is_new_frame = (header >> 7) & 0x01 != 0;
     let is_translated = (header >> 5) & 0x03 != 0;
     // If the frame is translated, we need to unpack the x and y values
     let translation_x =
-      if is_translated { self.stream.read_i8().unwrap() } else { 0 };
+      if is_translated { self.stream.read_i8().unwrap_or_default() } else { 0 };
     let translation_y =
-      if is_translated { self.stream.read_i8().unwrap() } else { 0 };
+      if is_translated { self.stream.read_i8().unwrap_or_default() } else { 0 };
     // Read line encoding bytes
     let line_types = [
       read_n_of_size_from_to_vec!(48, self, u8),
@@ -403,15 +789,18 @@ impl PPMParser {
             }
 
             // Unpack chunk usage
-            let mut chunk_usage =
-              self.stream.read_u32::<byteorder::BigEndian>().unwrap();
+            let mut chunk_usage = self
+              .stream
+              .read_u32::<byteorder::BigEndian>()
+              .unwrap_or_default();
 
             // Unpack pixel chunks
             while pixel < 256 {
               if chunk_usage & 0x8000_0000 == 0 {
                 pixel += 8;
               } else {
-                let chunk = self.stream.read_uint::<LittleEndian>(1).unwrap();
+                let chunk =
+                  self.stream.read_uint::<LittleEndian>(1).unwrap_or_default();
 
                 for bit in 0..8 {
                   bitmap[line][pixel] = (chunk >> bit & 0x1) as u8;
@@ -425,7 +814,8 @@ impl PPMParser {
           } else if line_type == 3 {
             // Unpack pixel chunks
             while pixel < 256 {
-              let chunk = self.stream.read_uint::<LittleEndian>(1).unwrap();
+              let chunk =
+                self.stream.read_uint::<LittleEndian>(1).unwrap_or_default();
 
               for bit in 0..8 {
                 bitmap[line][pixel] = (chunk >> bit & 0x1) as u8;
@@ -442,59 +832,72 @@ impl PPMParser {
     // If the current frame is based on the previous one, merge them by XOR-ing
     // their pixels. This is a big performance bottleneck...
     if !is_new_frame {
-      // Loop through lines
-      for y in 0..192 {
-        // Skip to next line if this one falls off the top edge of the screen
-        // if y - (translation_y as usize) < 0 {
-        //   continue;
-        // }
-        // Stop once the bottom screen edge has been reached
-        if y - translation_y as usize >= 192 {
-          break;
-        }
-
-        for x in 0..256 {
-          // Skip to the next pixel if this one falls off the left edge of the
-          // screen if x - (translation_x as usize) < 0 {
-          //   continue;
-          // }
-          // Stop diffing this line once the right screen edge has been reached
-          if x - translation_x as usize >= 256 {
-            break;
-          }
+      self.merge_previous_frame(translation_x, translation_y);
+    }
+  }
 
-          // Diff pixels with a binary XOR
-          self.layers[0][y][x] ^= self.prev_layers[0]
-            [y - translation_y as usize][x - translation_x as usize];
-          self.layers[1][y][x] ^= self.prev_layers[1]
-            [y - translation_y as usize][x - translation_x as usize];
-        }
+  /// XOR the previous frame, moved by the translation, onto the current one
+  fn merge_previous_frame(&mut self, translation_x: i8, translation_y: i8) {
+    // Loop through lines
+    for y in 0..192_usize {
+      // Skip to the next line if this one's source falls off the top or
+      // bottom edge of the screen
+      let Some(source_y) = y
+        .checked_add_signed(-isize::from(translation_y))
+        .filter(|&source_y| source_y < 192)
+      else {
+        continue;
+      };
+
+      for x in 0..256_usize {
+        // Skip to the next pixel if this one's source falls off the left or
+        // right edge of the screen
+        let Some(source_x) = x
+          .checked_add_signed(-isize::from(translation_x))
+          .filter(|&source_x| source_x < 256)
+        else {
+          continue;
+        };
+
+        // Diff pixels with a binary XOR
+        self.layers[0][y][x] ^= self.prev_layers[0][source_y][source_x];
+        self.layers[1][y][x] ^= self.prev_layers[1][source_y][source_x];
       }
     }
-
-    &self.layers
   }
+}
+impl Flipnote for PPMParser {
+  fn dimensions(&self) -> (usize, usize) { (256, 192) }
+
+  fn layer_count(&self) -> usize { 2 }
 
-  pub fn get_frame_palette(&mut self, index: usize) -> Vec<(u8, u8, u8)> {
-    self.stream.set_position(self.offset_table[index].into());
+  fn default_palette(&self) -> Palette { Palette::DSI }
 
-    let header = self.stream.read_uint::<LittleEndian>(1).unwrap();
+  fn get_frame_count(&self) -> u16 { self.frame_count }
+
+  fn get_thumb_index(&self) -> u16 { self.thumb_index }
+
+  fn get_framerate(&self) -> f64 { self.framerate }
+
+  fn get_frame_palette(
+    &mut self,
+    index: usize,
+    palette: &Palette,
+  ) -> Vec<(u8, u8, u8)> {
+    let header = self.read_frame_header(index);
     let paper_colour = header & 0x1;
-    let pen = [
-      None,
-      Some(if paper_colour == 1 { BLACK } else { WHITE }),
-      Some(RED),
-      Some(BLUE),
-    ];
+    let inverse = if paper_colour == 1 { palette.black } else { palette.white };
+    // Pen zero isn't used by Flipnote Studio, so it's drawn as pen one
+    let pen = [inverse, inverse, palette.red, palette.blue];
 
     vec![
-      if paper_colour == 1 { WHITE } else { BLACK },
-      pen.get(((header >> 1) & 0x3) as usize).unwrap().unwrap(), /* Layer one colour */
-      pen.get(((header >> 3) & 0x3) as usize).unwrap().unwrap(), /* Layer two colour */
+      if paper_colour == 1 { palette.white } else { palette.black },
+      pen[((header >> 1) & 0x3) as usize], // Layer one colour
+      pen[((header >> 3) & 0x3) as usize], // Layer two colour
     ]
   }
 
-  pub fn get_frame_pixels(&mut self, index: usize) -> Vec<Vec<u8>> {
+  fn get_frame_pixels(&mut self, index: usize) -> Vec<Vec<u8>> {
     let layers = self.read_frame(index);
     let mut pixels = vec![vec![0u8; 256]; 192];
 
@@ -512,42 +915,152 @@ impl PPMParser {
     pixels
   }
 
-  pub const fn get_frame_count(&self) -> u16 { self.frame_count }
-
-  pub const fn get_thumb_index(&self) -> u16 { self.thumb_index }
-
-  pub const fn get_framerate(&self) -> f64 { self.framerate }
-
-  pub fn dump_to_json(&self, filename: &str) {
-    let writer = std::io::BufWriter::new(fs::File::create(filename).unwrap());
-    serde_json::to_writer_pretty(
-      writer,
-      &serde_json::json!({
-        "animation_data_size": self.animation_data_size,
-        "sound_data_size": self.sound_data_size,
-        "frame_count": self.frame_count,
-        "lock": self.lock,
-        "thumb_index": self.thumb_index,
-        "root_author_name": self.root_author_name,
-        "parent_author_name": self.parent_author_name,
-        "current_author_name": self.current_author_name,
-        "root_author_id": self.root_author_id,
-        "parent_author_id": self.parent_author_id,
-        "current_author_id": self.current_author_id,
-        "parent_filename": self.parent_filename,
-        "current_filename": self.current_filename,
-        "partial_filename": self.partial_filename,
-        "timestamp": self.timestamp.to_string(),
-        "layer_1_visible": self.layer_1_visible,
-        "layer_2_visible": self.layer_2_visible,
-        "loop": self.loop_,
-        "frame_speed": self.frame_speed,
-        "bgm_speed": self.bgm_speed,
-        "framerate": self.framerate,
-        "bgm_framerate": self.bgm_framerate,
-      }),
-    )
-    .unwrap();
+  fn get_layer_pixels(&mut self, index: usize) -> Vec<Vec<Vec<u8>>> {
+    self
+      .read_frame(index)
+      .iter()
+      .zip(1..)
+      .map(|(layer, colour)| {
+        layer
+          .iter()
+          .map(|line| {
+            line
+              .iter()
+              .map(|&pixel| if pixel > 0 { colour } else { 0 })
+              .collect()
+          })
+          .collect()
+      })
+      .collect()
+  }
+
+  fn metadata(&self) -> Metadata {
+    Metadata {
+      authors:          [
+        (self.root_author_name.clone(), self.root_author_id.to_string()),
+        (self.parent_author_name.clone(), self.parent_author_id.to_string()),
+        (self.current_author_name.clone(), self.current_author_id.to_string()),
+      ],
+      parent_filename:  self.parent_filename.to_string(),
+      current_filename: self.current_filename.to_string(),
+      public_filename:  Some(self.current_filename.to_public()),
+      warnings:         [self.filename_issues(), self.author_id_issues()]
+        .concat(),
+      timestamp:        self.timestamp,
+      lock:             self.lock != 0,
+      loop_:            self.loop_,
+      frame_speed:      self.frame_speed,
+      bgm_speed:        u32::from(self.bgm_speed),
+      bgm_framerate:    self.bgm_framerate,
+      layer_visible:    vec![self.layer_1_visible, self.layer_2_visible],
+    }
+  }
+
+  fn metadata_json(&self) -> serde_json::Value {
+    let mut json = self.header_json();
+
+    json["layer_1_visible"] = self.layer_1_visible.into();
+    json["layer_2_visible"] = self.layer_2_visible.into();
+    json["loop"] = self.loop_.into();
+    json["frame_speed"] = self.frame_speed.into();
+    json["bgm_speed"] = self.bgm_speed.into();
+    json["framerate"] = self.framerate.into();
+    json["bgm_framerate"] = self.bgm_framerate.into();
+
+    json
+  }
+
+  /// Audio is stored as 4-bit ADPCM at 8192 Hz, so each byte holds two
+  /// samples
+  fn sound_tracks(&self) -> Vec<SoundTrack> {
+    ["bgm", "se1", "se2", "se3"]
+      .into_iter()
+      .zip(self.sound_sizes)
+      .map(|(name, size)| SoundTrack {
+        name,
+        size,
+        duration: f64::from(size) * 2.0 / 8192.0,
+      })
+      .collect()
+  }
+
+  fn sample_rate(&self) -> u32 { 8192 }
+
+  /// Tracks are stored one after another, directly after the sound header;
+  /// a track which runs past the end of the file is decoded as far as it goes
+  fn decode_sound_track(&self, index: usize) -> Vec<i16> {
+    let Some(&size) = self.sound_sizes.get(index) else {
+      return Vec::new();
+    };
+    let bytes = self.stream.get_ref();
+    let start = self.sound_header_offset()
+      + 0x20
+      + self.sound_sizes[..index].iter().copied().map(u64::from).sum::<u64>();
+    let start = usize::try_from(start).unwrap_or(usize::MAX).min(bytes.len());
+    let end = start
+      .saturating_add(usize::try_from(size).unwrap_or(usize::MAX))
+      .min(bytes.len());
+
+    adpcm::decode(&bytes[start..end])
+  }
+
+  /// The sound data section is measured by its tracks rather than the
+  /// header's `sound_data_size`, which some files leave at zero.
+  fn get_section_sizes(&self) -> Vec<(String, u64)> {
+    let sound_header_offset = self.sound_header_offset();
+    let animation_header_size = 8 + u64::from(self.table_size);
+    let sound_data_size =
+      self.sound_sizes.iter().copied().map(u64::from).sum::<u64>();
+    let sound_data_end = self.signature_offset();
+
+    vec![
+      ("header", 0x10),
+      ("metadata", 0x90),
+      ("thumbnail", 0x600),
+      ("animation_header", animation_header_size),
+      (
+        "frame_data",
+        u64::from(self.animation_data_size)
+          .saturating_sub(animation_header_size),
+      ),
+      (
+        "sound_effect_flags",
+        sound_header_offset
+          .saturating_sub(0x06A0 + u64::from(self.animation_data_size)),
+      ),
+      ("sound_header", 0x20),
+      ("sound_data", sound_data_size),
+      (
+        "signature",
+        (self.stream.get_ref().len() as u64).saturating_sub(sound_data_end),
+      ),
+    ]
+    .into_iter()
+    .map(|(name, size)| (name.to_string(), size))
+    .collect()
+  }
+
+  fn file_size(&self) -> usize { self.stream.get_ref().len() }
+
+  fn dump_json(&mut self, extended: bool) -> serde_json::Value {
+    let mut json = self.metadata_json();
+
+    if extended {
+      json["frames"] = (0..usize::from(self.frame_count))
+        .map(|index| {
+          if self.is_damaged(index) {
+            serde_json::json!({
+              "index": index,
+              "damaged": self.damaged_frames[&index],
+            })
+          } else {
+            self.get_frame_info(index).to_json(index)
+          }
+        })
+        .collect();
+    }
+
+    json
   }
 }
 impl Default for PPMParser {
@@ -565,12 +1078,12 @@ impl Default for PPMParser {
       root_author_name:               String::default(),
       parent_author_name:             String::default(),
       current_author_name:            String::default(),
-      parent_author_id:               String::default(),
-      current_author_id:              String::default(),
-      parent_filename:                String::default(),
-      current_filename:               String::default(),
-      root_author_id:                 String::default(),
-      partial_filename:               String::default(),
+      parent_author_id:               FlipnoteStudioId::default(),
+      current_author_id:              FlipnoteStudioId::default(),
+      parent_filename:                PpmFilename::default(),
+      current_filename:               PpmFilename::default(),
+      root_author_id:                 FlipnoteStudioId::default(),
+      partial_filename:               FilenameFragment::default(),
       #[allow(deprecated)]
       timestamp:                      TimeZone::from_utc_datetime(
         &Utc,
@@ -584,6 +1097,10 @@ impl Default for PPMParser {
       framerate:                      Default::default(),
       bgm_framerate:                  Default::default(),
       offset_table:                   Vec::default(),
+      table_size:                     Default::default(),
+      sound_sizes:                    Default::default(),
+      recovery:                       None,
+      damaged_frames:                 BTreeMap::new(),
     }
   }
 }
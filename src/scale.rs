@@ -0,0 +1,147 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+/// How a decoded frame should be upscaled before encoding
+///
+/// Scaling happens on palette indices rather than on RGB pixels, so the
+/// output keeps its original three-colour palette no matter the factor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scaling {
+  /// Nearest-neighbour scaling by an integer factor
+  Nearest(u8),
+  /// Pixel-art-aware smoothing using the Scale2x/Scale3x family
+  Smooth(u8),
+}
+impl Scaling {
+  /// Parse a `--scale` factor, optionally smoothed with `--smooth`
+  pub fn from_options(factor: &str, smooth: bool) -> Result<Self, String> {
+    let factor = factor
+      .parse::<u8>()
+      .map_err(|_| format!("invalid scale factor({factor})"))?;
+
+    if !(1..=4).contains(&factor) {
+      return Err(format!("invalid scale factor({factor}), expected 1..4"));
+    }
+
+    Ok(if smooth { Self::Smooth(factor) } else { Self::Nearest(factor) })
+  }
+
+  pub fn apply(self, pixels: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+    match self {
+      Self::Nearest(1) | Self::Smooth(1) => pixels,
+      Self::Nearest(factor) => nearest(&pixels, usize::from(factor)),
+      Self::Smooth(2) => scale2x(&pixels),
+      Self::Smooth(3) => scale3x(&pixels),
+      // Scale4x is just Scale2x applied twice
+      Self::Smooth(_) => scale2x(&scale2x(&pixels)),
+    }
+  }
+}
+impl Default for Scaling {
+  fn default() -> Self { Self::Nearest(1) }
+}
+
+/// Fetch a neighbouring pixel, clamping coordinates that fall off the edges of
+/// the bitmap
+fn neighbour(
+  pixels: &[Vec<u8>],
+  (x, y): (usize, usize),
+  (dx, dy): (isize, isize),
+) -> u8 {
+  let y = y.saturating_add_signed(dy).min(pixels.len() - 1);
+  let x = x.saturating_add_signed(dx).min(pixels[y].len() - 1);
+
+  pixels[y][x]
+}
+
+fn nearest(pixels: &[Vec<u8>], factor: usize) -> Vec<Vec<u8>> {
+  pixels
+    .iter()
+    .flat_map(|line| {
+      let scaled = line
+        .iter()
+        .flat_map(|&pixel| std::iter::repeat_n(pixel, factor))
+        .collect::<Vec<u8>>();
+
+      std::iter::repeat_n(scaled, factor)
+    })
+    .collect()
+}
+
+/// <https://www.scale2x.it/algorithm>
+#[allow(clippy::many_single_char_names)]
+fn scale2x(pixels: &[Vec<u8>]) -> Vec<Vec<u8>> {
+  let height = pixels.len();
+  let width = pixels.first().map_or(0, Vec::len);
+  let mut scaled = vec![vec![0; width * 2]; height * 2];
+
+  for y in 0..height {
+    for x in 0..width {
+      let a = neighbour(pixels, (x, y), (0, -1));
+      let b = neighbour(pixels, (x, y), (1, 0));
+      let c = neighbour(pixels, (x, y), (-1, 0));
+      let d = neighbour(pixels, (x, y), (0, 1));
+      let p = pixels[y][x];
+
+      scaled[y * 2][x * 2] = if c == a && c != d && a != b { a } else { p };
+      scaled[y * 2][x * 2 + 1] = if a == b && a != c && b != d { b } else { p };
+      scaled[y * 2 + 1][x * 2] = if d == c && d != b && c != a { c } else { p };
+      scaled[y * 2 + 1][x * 2 + 1] =
+        if b == d && b != a && d != c { d } else { p };
+    }
+  }
+
+  scaled
+}
+
+/// <https://www.scale2x.it/algorithm>
+#[allow(clippy::many_single_char_names)]
+fn scale3x(pixels: &[Vec<u8>]) -> Vec<Vec<u8>> {
+  let height = pixels.len();
+  let width = pixels.first().map_or(0, Vec::len);
+  let mut scaled = vec![vec![0; width * 3]; height * 3];
+
+  for y in 0..height {
+    for x in 0..width {
+      // A B C
+      // D E F
+      // G H I
+      let a = neighbour(pixels, (x, y), (-1, -1));
+      let b = neighbour(pixels, (x, y), (0, -1));
+      let c = neighbour(pixels, (x, y), (1, -1));
+      let d = neighbour(pixels, (x, y), (-1, 0));
+      let e = pixels[y][x];
+      let f = neighbour(pixels, (x, y), (1, 0));
+      let g = neighbour(pixels, (x, y), (-1, 1));
+      let h = neighbour(pixels, (x, y), (0, 1));
+      let i = neighbour(pixels, (x, y), (1, 1));
+      let block = if b != h && d != f {
+        [
+          [
+            if d == b { d } else { e },
+            if (d == b && e != c) || (b == f && e != a) { b } else { e },
+            if b == f { f } else { e },
+          ],
+          [
+            if (d == b && e != g) || (d == h && e != a) { d } else { e },
+            e,
+            if (b == f && e != i) || (h == f && e != c) { f } else { e },
+          ],
+          [
+            if d == h { d } else { e },
+            if (d == h && e != i) || (h == f && e != g) { h } else { e },
+            if h == f { f } else { e },
+          ],
+        ]
+      } else {
+        [[e; 3]; 3]
+      };
+
+      for (row_index, row) in block.iter().enumerate() {
+        scaled[y * 3 + row_index][x * 3..x * 3 + 3].copy_from_slice(row);
+      }
+    }
+  }
+
+  scaled
+}
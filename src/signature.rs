@@ -0,0 +1,76 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use {
+  rsa::{
+    Pkcs1v15Sign, RsaPublicKey, pkcs1::DecodeRsaPublicKey,
+    pkcs8::DecodePublicKey,
+  },
+  sha1::{Digest, Sha1},
+};
+
+/// Flipnote Studio signs with a 1024-bit RSA key, so signatures are 0x80
+/// bytes, followed by 0x10 bytes of padding
+pub const SIGNATURE_SIZE: usize = 0x80;
+
+/// Load an RSA public key from a PEM file, either as a bare PKCS#1 key
+/// (`RSA PUBLIC KEY`) or wrapped with its algorithm (`PUBLIC KEY`)
+pub fn load_public_key(path: &str) -> Result<RsaPublicKey, String> {
+  let pem = std::fs::read_to_string(path)
+    .map_err(|error| format!("could not read {path}: {error}"))?;
+
+  RsaPublicKey::from_pkcs1_pem(&pem)
+    .or_else(|_| RsaPublicKey::from_public_key_pem(&pem))
+    .map_err(|error| format!("invalid public key({path}): {error}"))
+}
+
+/// The RSA-SHA1 signature at the end of a file, alongside the data it covers
+pub struct Signature<'a> {
+  signed:   &'a [u8],
+  /// Everything after the signed data; the signature and its padding
+  trailing: &'a [u8],
+}
+impl<'a> Signature<'a> {
+  /// Split a file into its signed data and whatever follows it at `offset`
+  pub fn new(file: &'a [u8], offset: usize) -> Self {
+    let (signed, trailing) = file.split_at(offset.min(file.len()));
+
+    Self { signed, trailing }
+  }
+
+  pub const fn offset(&self) -> usize { self.signed.len() }
+
+  /// The signature's bytes, if the file is long enough to hold one
+  pub fn bytes(&self) -> Option<&'a [u8]> {
+    self.trailing.get(..SIGNATURE_SIZE)
+  }
+
+  /// Whether the file holds a signature that hasn't been stripped or zeroed
+  pub fn is_present(&self) -> bool {
+    self.bytes().is_some_and(|bytes| bytes.iter().any(|&byte| byte != 0))
+  }
+
+  /// The SHA-1 digest of the signed data
+  pub fn sha1(&self) -> [u8; 20] { Sha1::digest(self.signed).into() }
+
+  /// Whether the signature was made over the signed data by the private
+  /// half of `key`
+  pub fn verify(&self, key: &RsaPublicKey) -> bool {
+    self.bytes().is_some_and(|signature| {
+      key.verify(Pkcs1v15Sign::new::<Sha1>(), &self.sha1(), signature).is_ok()
+    })
+  }
+
+  /// The file without its signature and padding
+  pub fn stripped(&self) -> Vec<u8> { self.signed.to_vec() }
+
+  /// The file with its signature and padding overwritten with zeroes,
+  /// keeping its size
+  pub fn zeroed(&self) -> Vec<u8> {
+    let mut file = self.signed.to_vec();
+
+    file.resize(self.signed.len() + self.trailing.len(), 0);
+
+    file
+  }
+}
@@ -0,0 +1,355 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+use {
+  crate::{
+    export::IndexedFrame,
+    ppm::{PPMParser, TMB_SIZE},
+  },
+  base64::{Engine, engine::general_purpose::STANDARD},
+  byteorder::{LittleEndian, ReadBytesExt},
+  std::{
+    fmt::Write,
+    fs,
+    io::{Cursor, Read},
+  },
+};
+
+const STYLE: &str =
+  "body { font-family: sans-serif; max-width: 40em; margin: auto; } nav a { \
+   margin-right: 1em; } nav .selected { font-weight: bold; } main { display: \
+   flex; flex-wrap: wrap; gap: 0.5em; } main.layout-0 { flex-direction: \
+   column; } main a { display: flex; align-items: center; gap: 0.5em; } img { \
+   image-rendering: pixelated; width: 128px; }";
+
+/// Whether a file is a `.ugo` menu
+pub fn is_ugo(path: &str) -> bool {
+  crate::flipnote::read_magic(path).is_some_and(|magic| &magic == b"UGAR")
+}
+
+/// Read `n` bytes, checking that the menu holds them before allocating
+#[allow(clippy::cast_possible_truncation)]
+fn read_n_to_vec(
+  stream: &mut Cursor<Vec<u8>>,
+  n: usize,
+  what: &str,
+) -> Result<Vec<u8>, String> {
+  let remaining =
+    stream.get_ref().len().saturating_sub(stream.position() as usize);
+
+  if n > remaining {
+    return Err(format!(
+      "truncated {what}: expected {n} bytes, found {remaining}"
+    ));
+  }
+
+  let mut buffer = vec![0; n];
+
+  stream.read_exact(&mut buffer).map_err(|error| error.to_string())?;
+
+  Ok(buffer)
+}
+
+fn read_u32(stream: &mut Cursor<Vec<u8>>) -> Result<u32, String> {
+  stream
+    .read_u32::<LittleEndian>()
+    .map_err(|_| "truncated section table".to_string())
+}
+
+/// Labels are stored as Base64-encoded UTF-16LE
+fn decode_label(value: &str) -> String {
+  let bytes = STANDARD.decode(value).unwrap_or_default();
+
+  String::from_utf16_lossy(
+    &bytes
+      .chunks_exact(2)
+      .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+      .collect::<Vec<_>>(),
+  )
+}
+
+fn parse_number(field: Option<&&str>) -> u32 {
+  field.and_then(|field| field.parse().ok()).unwrap_or_default()
+}
+
+fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn data_uri(frame: &IndexedFrame) -> String {
+  let mut png = Cursor::new(Vec::new());
+
+  frame
+    .to_image(false)
+    .write_to(&mut png, image::ImageOutputFormat::Png)
+    .unwrap();
+
+  format!("data:image/png;base64,{}", STANDARD.encode(png.into_inner()))
+}
+
+/// A Flipnote's `.tmb` embedded in a menu for the button linking to it
+pub struct EmbeddedTmb {
+  /// The header and metadata, as given by [`PPMParser::header_json`]
+  pub metadata:  serde_json::Value,
+  pub thumbnail: IndexedFrame,
+}
+
+/// An entry of a menu's table of contents
+///
+/// <https://github.com/pbsds/hatena-server/wiki/UGO-format>
+pub enum Item {
+  /// How the menu's buttons are laid out on the bottom screen
+  Layout(Vec<u32>),
+  /// Text shown on the top screen
+  TopScreen {
+    kind:   u32,
+    labels: Vec<String>,
+  },
+  /// A tab along the top of the bottom screen
+  Category {
+    url:      String,
+    label:    String,
+    selected: bool,
+  },
+  /// A button which submits a form
+  Post {
+    url:   String,
+    label: String,
+  },
+  /// A button linking to another menu or to a Flipnote, in which case the
+  /// Flipnote's `.tmb` is embedded in the menu
+  Button {
+    url:   String,
+    icon:  u32,
+    label: String,
+    /// Trailing fields, e.g., a Flipnote's star count
+    extra: Vec<String>,
+    tmb:   Option<EmbeddedTmb>,
+  },
+  Unknown(Vec<String>),
+}
+impl Item {
+  pub fn to_json(&self) -> serde_json::Value {
+    match self {
+      Self::Layout(values) =>
+        serde_json::json!({ "type": "layout", "values": values }),
+      Self::TopScreen { kind, labels } => serde_json::json!({
+        "type": "top_screen",
+        "kind": kind,
+        "labels": labels,
+      }),
+      Self::Category { url, label, selected } => serde_json::json!({
+        "type": "category",
+        "url": url,
+        "label": label,
+        "selected": selected,
+      }),
+      Self::Post { url, label } =>
+        serde_json::json!({ "type": "post", "url": url, "label": label }),
+      Self::Button { url, icon, label, extra, tmb } => serde_json::json!({
+        "type": "button",
+        "url": url,
+        "icon": icon,
+        "label": label,
+        "extra": extra,
+        "tmb": tmb.as_ref().map(|tmb| serde_json::json!({
+          "metadata": tmb.metadata,
+          "thumbnail": data_uri(&tmb.thumbnail),
+        })),
+      }),
+      Self::Unknown(fields) =>
+        serde_json::json!({ "type": "unknown", "fields": fields }),
+    }
+  }
+}
+
+/// Decoder for Flipnote Hatena's `.ugo` menus
+///
+/// A menu is a table of contents of tab-separated lines, followed by the
+/// files embedded for its entries.
+///
+/// <https://github.com/pbsds/hatena-server/wiki/UGO-format>
+#[derive(Default)]
+pub struct UGOParser {
+  stream: Cursor<Vec<u8>>,
+  items:  Vec<Item>,
+}
+impl UGOParser {
+  #[allow(unused)]
+  pub fn new(stream: Vec<u8>) -> Self {
+    Self { stream: Cursor::new(stream), ..Self::default() }
+  }
+
+  pub fn new_from_file(file: &str) -> Self {
+    Self {
+      stream: Cursor::new(std::fs::read(file).unwrap()),
+      ..Self::default()
+    }
+  }
+
+  /// Read the table of contents and the `.tmb`s embedded for its buttons,
+  /// failing if the menu is cut short
+  pub fn load(&mut self) -> Result<(), String> {
+    // Skip the magic
+    self.stream.set_position(4);
+
+    // The table of contents, optionally followed by the embedded files
+    let section_count = read_u32(&mut self.stream)?;
+    let section_sizes = (0..section_count)
+      .map(|_| read_u32(&mut self.stream))
+      .collect::<Result<Vec<_>, _>>()?;
+    let table = read_n_to_vec(
+      &mut self.stream,
+      section_sizes.first().copied().unwrap_or_default() as usize,
+      "table of contents",
+    )?;
+
+    self.items = String::from_utf8_lossy(&table)
+      .trim_end_matches(char::from(0))
+      .split('\n')
+      .filter(|line| !line.is_empty())
+      .map(|line| self.read_item(&line.split('\t').collect::<Vec<_>>()))
+      .collect::<Result<_, _>>()?;
+
+    Ok(())
+  }
+
+  fn read_item(&mut self, fields: &[&str]) -> Result<Item, String> {
+    let field = |index: usize| fields.get(index).copied().unwrap_or_default();
+
+    Ok(match field(0) {
+      "0" => Item::Layout(
+        fields[1..].iter().map(|value| parse_number(Some(value))).collect(),
+      ),
+      "1" => Item::TopScreen {
+        kind:   parse_number(fields.get(1)),
+        labels: fields
+          .iter()
+          .skip(2)
+          .map(|label| decode_label(label))
+          .collect(),
+      },
+      "2" => Item::Category {
+        url:      field(1).to_string(),
+        label:    decode_label(field(2)),
+        selected: field(3) == "1",
+      },
+      "3" => Item::Post {
+        url:   field(1).to_string(),
+        label: decode_label(field(2)),
+      },
+      "4" => Item::Button {
+        url:   field(1).to_string(),
+        icon:  parse_number(fields.get(2)),
+        label: decode_label(field(3)),
+        extra: fields.iter().skip(4).map(ToString::to_string).collect(),
+        // Embedded files are stored in the same order as the buttons they
+        // belong to
+        tmb:   std::path::Path::new(field(1))
+          .extension()
+          .is_some_and(|extension| extension.eq_ignore_ascii_case("ppm"))
+          .then(|| self.read_embedded_tmb())
+          .transpose()?,
+      },
+      _ => Item::Unknown(fields.iter().map(ToString::to_string).collect()),
+    })
+  }
+
+  #[allow(clippy::cast_possible_truncation)]
+  fn read_embedded_tmb(&mut self) -> Result<EmbeddedTmb, String> {
+    let mut parser = PPMParser::new(read_n_to_vec(
+      &mut self.stream,
+      TMB_SIZE as usize,
+      "embedded .tmb",
+    )?);
+
+    parser.load_metadata();
+
+    Ok(EmbeddedTmb {
+      metadata:  parser.header_json(),
+      thumbnail: parser.get_thumbnail(),
+    })
+  }
+
+  pub fn to_json(&self) -> serde_json::Value {
+    serde_json::json!({
+      "items": self.items.iter().map(Item::to_json).collect::<Vec<_>>(),
+    })
+  }
+
+  /// Render the menu as a standalone HTML page; the top screen as a header,
+  /// categories as navigation, and buttons with their embedded thumbnails
+  pub fn to_html(&self) -> String {
+    let mut header = String::new();
+    let mut navigation = String::new();
+    let mut buttons = String::new();
+    let mut layout = 0;
+
+    for item in &self.items {
+      match item {
+        Item::Layout(values) => layout = values.first().copied().unwrap_or(0),
+        Item::TopScreen { labels, .. } =>
+          for label in labels.iter().filter(|label| !label.is_empty()) {
+            let _ = writeln!(header, "<p>{}</p>", escape(label));
+          },
+        Item::Category { url, label, selected } => {
+          let _ = writeln!(
+            navigation,
+            "<a href=\"{}\"{}>{}</a>",
+            escape(url),
+            if *selected { " class=\"selected\"" } else { "" },
+            escape(label)
+          );
+        }
+        Item::Post { url, label } => {
+          let _ = writeln!(
+            buttons,
+            "<form method=\"post\" action=\"{}\"><button>{}</button></form>",
+            escape(url),
+            escape(label)
+          );
+        }
+        Item::Button { url, label, extra, tmb, .. } => {
+          let _ = writeln!(
+            buttons,
+            "<a href=\"{}\">{}<span>{}</span><small>{}</small></a>",
+            escape(url),
+            tmb.as_ref().map_or_else(String::new, |tmb| format!(
+              "<img src=\"{}\" alt=\"\">",
+              data_uri(&tmb.thumbnail)
+            )),
+            escape(label),
+            escape(&extra.join(" "))
+          );
+        }
+        Item::Unknown(_) => {}
+      }
+    }
+
+    let mut page = String::new();
+
+    let _ = writeln!(page, "<!DOCTYPE html>\n<html>\n<head>");
+    let _ = writeln!(page, "<meta charset=\"utf-8\">");
+    let _ = writeln!(page, "<style>{STYLE}</style>\n</head>\n<body>");
+    let _ = writeln!(page, "<header>\n{header}</header>");
+    let _ = writeln!(page, "<nav>\n{navigation}</nav>");
+    let _ =
+      writeln!(page, "<main class=\"layout-{layout}\">\n{buttons}</main>");
+    let _ = writeln!(page, "</body>\n</html>");
+
+    page
+  }
+
+  pub fn dump_to_json(&self, filename: &str) {
+    let writer = std::io::BufWriter::new(fs::File::create(filename).unwrap());
+    serde_json::to_writer_pretty(writer, &self.to_json()).unwrap();
+  }
+
+  pub fn dump_to_html(&self, filename: &str) {
+    fs::write(filename, self.to_html()).unwrap();
+  }
+}
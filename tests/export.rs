@@ -0,0 +1,255 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Encodes frames as GIFs and APNGs, with and without transparent paper, and
+//! decodes them again to check their frames, delays, and palettes
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+#![allow(clippy::cast_possible_truncation)]
+
+use {
+  para_cli::{
+    export::{self, IndexedFrame},
+    flipnote::Flipnote,
+    palette::Palette,
+    ppm::PPMParser,
+  },
+  std::{
+    fs,
+    path::{Path, PathBuf},
+  },
+};
+
+const WHITE: (u8, u8, u8) = (255, 255, 255);
+const BLACK: (u8, u8, u8) = (0, 0, 0);
+const RED: (u8, u8, u8) = (255, 0, 0);
+const BLUE: (u8, u8, u8) = (0, 0, 255);
+
+fn temp_path(name: &str) -> PathBuf {
+  std::env::temp_dir()
+    .join(format!("para-export-{}-{name}", std::process::id()))
+}
+
+/// Two frames with different palettes, as frames with different pen colours
+/// have
+fn frames() -> Vec<IndexedFrame> {
+  vec![
+    IndexedFrame {
+      pixels:  vec![vec![0, 1, 2, 0], vec![1, 1, 0, 2]],
+      colours: vec![WHITE, BLACK, RED],
+    },
+    IndexedFrame {
+      pixels:  vec![vec![2, 2, 1, 0], vec![0, 0, 1, 1]],
+      colours: vec![BLACK, WHITE, BLUE],
+    },
+  ]
+}
+
+/// Every frame of a GIF, as palette indices
+fn decode_gif(path: &Path) -> (gif::Repeat, Vec<gif::Frame<'static>>) {
+  let mut options = gif::DecodeOptions::new();
+
+  options.set_color_output(gif::ColorOutput::Indexed);
+
+  let mut decoder = options.read_info(fs::File::open(path).unwrap()).unwrap();
+  let mut frames = Vec::new();
+
+  while let Some(frame) = decoder.read_next_frame().unwrap() {
+    frames.push(frame.clone());
+  }
+
+  (decoder.repeat(), frames)
+}
+
+#[test]
+fn gif() {
+  for transparent in [false, true] {
+    let path = temp_path(&format!("{transparent}.gif"));
+    let frames = frames();
+
+    export::write_gif(&path.to_string_lossy(), &frames, 8, transparent);
+
+    let (repeat, decoded) = decode_gif(&path);
+
+    fs::remove_file(&path).unwrap();
+    assert_eq!(repeat, gif::Repeat::Infinite);
+    assert_eq!(decoded.len(), frames.len());
+
+    // Each frame keeps its own palette, with the paper transparent if asked
+    for (frame, decoded) in frames.iter().zip(&decoded) {
+      assert_eq!(decoded.delay, 8);
+      assert_eq!(decoded.transparent, transparent.then_some(0));
+      assert_eq!(decoded.dispose, gif::DisposalMethod::Background);
+      assert_eq!((decoded.width, decoded.height), (4, 2));
+      assert_eq!(*decoded.buffer, frame.pixels.concat());
+      assert_eq!(
+        decoded.palette.as_deref().unwrap()[..frame.colours.len() * 3],
+        frame
+          .colours
+          .iter()
+          .copied()
+          .flat_map(<[u8; 3]>::from)
+          .collect::<Vec<_>>()
+      );
+    }
+  }
+}
+
+#[test]
+fn apng() {
+  for transparent in [false, true] {
+    let path = temp_path(&format!("{transparent}.png"));
+    let frames = frames();
+
+    export::write_apng(&path.to_string_lossy(), &frames, 12.0, transparent);
+
+    let mut decoder = png::Decoder::new(fs::File::open(&path).unwrap());
+
+    decoder.set_transformations(png::Transformations::IDENTITY);
+
+    let mut reader = decoder.read_info().unwrap();
+    let info = reader.info();
+    let animation = info.animation_control.unwrap();
+    let palette = info
+      .palette
+      .as_deref()
+      .unwrap()
+      .chunks_exact(3)
+      .map(|colour| (colour[0], colour[1], colour[2]))
+      .collect::<Vec<_>>();
+
+    assert_eq!(info.color_type, png::ColorType::Indexed);
+    assert_eq!(animation.num_frames, 2);
+    assert_eq!(animation.num_plays, 0);
+    assert_eq!(info.trns.as_deref(), transparent.then_some(&[0][..]));
+
+    for frame in &frames {
+      let mut buffer = vec![0; reader.output_buffer_size()];
+
+      reader.next_frame(&mut buffer).unwrap();
+
+      let control = reader.info().frame_control.unwrap();
+
+      // A tenth of a second per `delay_num`, at 12 frames per second
+      assert_eq!((control.delay_num, control.delay_den), (10, 120));
+      assert_eq!(control.dispose_op, png::DisposeOp::Background);
+
+      // Frames share one palette, so their indices are remapped into it
+      for (&pixel, &index) in frame.pixels.iter().flatten().zip(&buffer) {
+        if transparent && pixel == 0 {
+          assert_eq!(index, 0);
+        } else {
+          assert_eq!(
+            palette[usize::from(index)],
+            frame.colours[usize::from(pixel)]
+          );
+        }
+      }
+    }
+
+    fs::remove_file(&path).unwrap();
+  }
+}
+
+#[test]
+fn transparent_paper() {
+  let frame = &frames()[0];
+  let opaque = frame.to_image(false).to_rgba8();
+  let transparent = frame.to_image(true).to_rgba8();
+
+  for (y, row) in frame.pixels.iter().enumerate() {
+    for (x, &pixel) in row.iter().enumerate() {
+      let (x, y) = (x as u32, y as u32);
+      let [red, green, blue] =
+        <[u8; 3]>::from(frame.colours[usize::from(pixel)]);
+
+      assert_eq!(opaque.get_pixel(x, y).0, [red, green, blue, 255]);
+      assert_eq!(transparent.get_pixel(x, y).0, [
+        red,
+        green,
+        blue,
+        if pixel == 0 { 0 } else { 255 }
+      ]);
+    }
+  }
+}
+
+/// Each layer is exported on its own canvas, with its ink in its own palette
+/// index, so that layers stacked in order give the composited frame
+#[test]
+fn layers() {
+  let mut parser = PPMParser::new(
+    fs::read(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("ppms/mrjohn.ppm"))
+      .unwrap(),
+  );
+
+  parser.load();
+
+  let parser: &mut dyn Flipnote = &mut parser;
+  let composited = parser
+    .frames(Palette::DSI, false)
+    .take(3)
+    .map(|mut frames| frames.remove(0))
+    .collect::<Vec<_>>();
+  let layers = parser.frames(Palette::DSI, true).take(3).collect::<Vec<_>>();
+
+  for (frame, layers) in composited.iter().zip(&layers) {
+    assert_eq!(layers.len(), parser.layer_count());
+
+    for (index, layer) in layers.iter().enumerate() {
+      assert_eq!(layer.colours, frame.colours);
+      assert!(
+        layer
+          .pixels
+          .iter()
+          .flatten()
+          .all(|&pixel| pixel == 0 || usize::from(pixel) == index + 1)
+      );
+    }
+
+    for (y, row) in frame.pixels.iter().enumerate() {
+      for (x, &pixel) in row.iter().enumerate() {
+        let stacked = layers
+          .iter()
+          .map(|layer| layer.pixels[y][x])
+          .find(|&pixel| pixel != 0)
+          .unwrap_or(0);
+
+        assert_eq!(pixel, stacked, "({x}, {y})");
+      }
+    }
+  }
+
+  // Each layer's frames are written to their own transparent GIF
+  for layer in 0..parser.layer_count() {
+    let path = temp_path(&format!("layer{layer}.gif"));
+    let frames = layers
+      .iter()
+      .map(|frames| IndexedFrame {
+        pixels:  frames[layer].pixels.clone(),
+        colours: frames[layer].colours.clone(),
+      })
+      .collect::<Vec<_>>();
+
+    export::write_gif(&path.to_string_lossy(), &frames, 5, true);
+
+    let (_, decoded) = decode_gif(&path);
+
+    fs::remove_file(&path).unwrap();
+    assert_eq!(decoded.len(), 3);
+
+    for (frame, decoded) in frames.iter().zip(decoded) {
+      assert_eq!(decoded.transparent, Some(0));
+      assert_eq!(decoded.delay, 5);
+      assert_eq!(*decoded.buffer, frame.pixels.concat());
+    }
+  }
+}
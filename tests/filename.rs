@@ -0,0 +1,117 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Checks public filenames and their checksums against the filenames of the
+//! sample memos in `ppms/`
+//!
+//! <https://github.com/pbsds/hatena-server/wiki/PPM-format#filenames>
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+
+use para_cli::filename::{PpmFilename, check_file_name};
+
+/// Stored filenames, with the public filenames the hatena-server wiki's
+/// `calc_check_digit` gives them
+const FILENAMES: [(&str, &str); 2] = [
+  ("17A554_0E2AF369A1323_000", "T7A554_0E2AF369A1323_000"),
+  ("D7D1EE_087CBDF2EF4BD_000", "H7D1EE_087CBDF2EF4BD_000"),
+];
+
+/// Parse a filename formatted as `<MAC>_<random>_<edits>` back into the
+/// eighteen bytes it's stored as
+fn stored(name: &str) -> PpmFilename {
+  let mut bytes = [0; 18];
+
+  for (index, byte) in bytes[..3].iter_mut().enumerate() {
+    *byte = u8::from_str_radix(&name[index * 2..index * 2 + 2], 16).unwrap();
+  }
+
+  bytes[3..16].copy_from_slice(&name.as_bytes()[7..20]);
+  bytes[16..]
+    .copy_from_slice(&name[21..].parse::<u16>().unwrap().to_le_bytes());
+
+  PpmFilename::from_stored(&bytes)
+}
+
+#[test]
+fn checksums() {
+  for (name, public) in FILENAMES {
+    let filename = stored(name);
+
+    assert_eq!(filename.to_string(), name);
+    assert_eq!(filename.checksum(), char::from(public.as_bytes()[0]));
+    assert_eq!(filename.to_public(), public);
+  }
+}
+
+#[test]
+fn valid_public() {
+  for (name, public) in FILENAMES {
+    let first_digit = name.as_bytes()[0];
+
+    assert!(PpmFilename::is_valid_public(public, first_digit));
+    assert!(PpmFilename::is_valid_public(
+      &public.to_ascii_lowercase(),
+      first_digit
+    ));
+    // The stored form's first digit isn't its checksum
+    assert!(!PpmFilename::is_valid_public(name, first_digit));
+
+    // Every other check digit is rejected
+    for digit in b"0123456789ABCDEFGHIJKLMNOPQRSTUV" {
+      let mut other = public.to_string();
+
+      other.replace_range(..1, &char::from(*digit).to_string());
+
+      assert_eq!(
+        PpmFilename::is_valid_public(&other, first_digit),
+        other == public,
+        "{other}"
+      );
+    }
+  }
+
+  for malformed in [
+    "T7A554_0E2AF369A1323_00",
+    "T7A554-0E2AF369A1323_000",
+    "T7A554_0E2AF369A1323_0X0",
+    "T7G554_0E2AF369A1323_000",
+  ] {
+    assert!(!PpmFilename::is_valid_public(malformed, b'1'), "{malformed}");
+  }
+}
+
+#[test]
+fn file_names() {
+  let (name, public) = FILENAMES[0];
+
+  assert_eq!(
+    check_file_name(&format!("memos/{public}.ppm"), name, public),
+    None
+  );
+  assert_eq!(check_file_name("memos/mrjohn.ppm", name, public), None);
+  assert_eq!(
+    check_file_name("memos/A7A554_0E2AF369A1323_000.ppm", name, public),
+    Some("file name A7A554_0E2AF369A1323_000 has an invalid checksum".into())
+  );
+
+  let (other, other_public) = FILENAMES[1];
+
+  assert_eq!(
+    check_file_name(&format!("{other_public}.ppm"), name, public),
+    Some(format!("file name {other_public} does not match {public}"))
+  );
+  // Validated with the first digit of the Flipnote's own filename
+  assert!(
+    check_file_name(&format!("{other_public}.ppm"), other, other_public)
+      .is_none()
+  );
+}
@@ -0,0 +1,81 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Checks Flipnote Studio IDs against the author IDs of the sample memos in
+//! `ppms/`
+//!
+//! <https://github.com/pbsds/hatena-server/wiki/PPM-format#header>
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+
+use para_cli::fsid::FlipnoteStudioId;
+
+/// Parse an ID as Flipnote Hatena displayed it back into the eight bytes it's
+/// stored as
+fn stored(id: &str) -> FlipnoteStudioId {
+  // Stored little-endian, as a `u64` is
+  FlipnoteStudioId::from_stored(
+    u64::from_str_radix(id, 16).unwrap().to_le_bytes(),
+  )
+}
+
+#[test]
+fn display() {
+  let id = FlipnoteStudioId::from_stored([
+    0x54, 0xA5, 0x17, 0x1E, 0x30, 0xA1, 0x03, 0x9C,
+  ]);
+
+  assert_eq!(id.to_string(), "9C03A1301E17A554");
+  assert_eq!(id, stored("9C03A1301E17A554"));
+  assert_eq!(id.mac_hex(), "17A554");
+  assert_eq!(stored("0ACBDD504CD7D1EE").mac_hex(), "D7D1EE");
+  assert_eq!(
+    serde_json::to_value(id).unwrap(),
+    serde_json::json!("9C03A1301E17A554")
+  );
+}
+
+#[test]
+fn is_valid() {
+  for id in ["9C03A1301E17A554", "0ACBDD504CD7D1EE"] {
+    assert!(stored(id).is_valid(), "{id}");
+  }
+
+  // Every first digit an ID can start with
+  for first in ['0', '1', '5', '9'] {
+    let id = format!("{first}C03A1301E17A554");
+
+    assert!(stored(&id).is_valid(), "{id}");
+  }
+
+  // The first digit is only ever 0, 1, 5, or 9, and the eighth is always 0
+  for id in [
+    "2C03A1301E17A554",
+    "FC03A1301E17A554",
+    "9C03A1311E17A554",
+    "9C03A13F1E17A554",
+  ] {
+    assert!(!stored(id).is_valid(), "{id}");
+  }
+}
+
+#[test]
+fn is_placeholder() {
+  let placeholder = FlipnoteStudioId::default();
+
+  assert!(placeholder.is_placeholder());
+  assert_eq!(placeholder, FlipnoteStudioId::from_stored([0; 8]));
+  assert_eq!(placeholder.to_string(), "0000000000000000");
+
+  for id in ["9C03A1301E17A554", "0000000000000001", "1000000000000000"] {
+    assert!(!stored(id).is_placeholder(), "{id}");
+  }
+}
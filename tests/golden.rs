@@ -0,0 +1,162 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Decodes the sample memos in `ppms/` and compares every frame, palette,
+//! sound track, thumbnail, and metadata field against the references in
+//! `tests/golden/`
+//!
+//! After an intended change to decoding, regenerate the references with
+//! `PARA_BLESS=1 cargo test --test golden` and review their diff.
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+
+use {
+  para_cli::{flipnote::Flipnote, palette::Palette, ppm::PPMParser},
+  sha1::{Digest, Sha1},
+  std::{fmt::Write, fs, path::PathBuf},
+};
+
+fn manifest_path(path: &str) -> PathBuf {
+  PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+fn load(name: &str) -> PPMParser {
+  let mut parser = PPMParser::new(
+    fs::read(manifest_path(&format!("ppms/{name}.ppm"))).unwrap(),
+  );
+
+  parser.load();
+
+  parser
+}
+
+fn hash(pixels: &[Vec<u8>]) -> String {
+  let mut hasher = Sha1::new();
+
+  pixels.iter().for_each(|row| hasher.update(row));
+
+  hasher.finalize().iter().fold(String::new(), |mut hash, byte| {
+    let _ = write!(hash, "{byte:02x}");
+
+    hash
+  })
+}
+
+/// One line for the thumbnail, and one for each frame with the hashes of its
+/// composited pixels and each of its layers, followed by its colours, and one
+/// for each sound track with the hash of its decoded samples
+fn describe(parser: &mut PPMParser) -> String {
+  let mut description = String::new();
+
+  let _ =
+    writeln!(description, "thumbnail {}", hash(&parser.get_thumbnail().pixels));
+
+  for index in 0..usize::from(parser.get_frame_count()) {
+    let pixels = parser.get_frame_pixels(index);
+    let layers = parser.get_layer_pixels(index);
+
+    let _ = write!(
+      description,
+      "frame {index} {} {} {}",
+      hash(&pixels),
+      hash(&layers[0]),
+      hash(&layers[1])
+    );
+    for (red, green, blue) in parser.get_frame_palette(index, &Palette::DSI) {
+      let _ = write!(description, " {red:02X}{green:02X}{blue:02X}");
+    }
+    let _ = writeln!(description);
+  }
+
+  for (index, track) in parser.sound_tracks().iter().enumerate() {
+    let samples = parser.decode_sound_track(index);
+
+    let _ = writeln!(
+      description,
+      "sound {} {} {}",
+      track.name,
+      samples.len(),
+      hash(&[samples.iter().flat_map(|sample| sample.to_le_bytes()).collect()])
+    );
+  }
+
+  description
+}
+
+/// Compare output against its reference, or replace the reference when
+/// `PARA_BLESS` is set
+fn assert_golden(reference: &str, actual: &str) {
+  let path = manifest_path(&format!("tests/golden/{reference}"));
+
+  if std::env::var_os("PARA_BLESS").is_some() {
+    fs::write(&path, actual).unwrap();
+
+    return;
+  }
+
+  let expected = fs::read_to_string(&path).unwrap_or_else(|error| {
+    panic!("could not read {}: {error}", path.display())
+  });
+
+  for (line, (expected, actual)) in
+    expected.lines().zip(actual.lines()).enumerate()
+  {
+    assert_eq!(
+      expected,
+      actual,
+      "{reference} differs at line {}; rerun with PARA_BLESS=1 if this is \
+       intended",
+      line + 1
+    );
+  }
+
+  assert_eq!(
+    expected.lines().count(),
+    actual.lines().count(),
+    "{reference} differs in length"
+  );
+}
+
+fn assert_memo(name: &str) {
+  let mut parser = load(name);
+
+  assert_golden(&format!("{name}.frames"), &describe(&mut parser));
+  assert_golden(
+    &format!("{name}.json"),
+    &(serde_json::to_string_pretty(&parser.dump_json(true)).unwrap() + "\n"),
+  );
+  assert_golden(
+    &format!("{name}.info.json"),
+    &(serde_json::to_string_pretty(&parser.info_json()).unwrap() + "\n"),
+  );
+}
+
+#[test]
+fn mrjohn() { assert_memo("mrjohn"); }
+
+#[test]
+fn samplememo_02() { assert_memo("samplememo_02"); }
+
+/// Seeking to a frame decodes the frames it's based on, which should give
+/// the same pixels as playing through them
+#[test]
+fn random_access_matches_playback() {
+  let mut playback = load("samplememo_02");
+  let mut seeking = load("samplememo_02");
+  let frame_count = usize::from(playback.get_frame_count());
+  let frames = (0..frame_count)
+    .map(|index| playback.get_frame_pixels(index))
+    .collect::<Vec<_>>();
+
+  for index in (0..frame_count).rev().step_by(7) {
+    assert_eq!(seeking.get_frame_pixels(index), frames[index], "frame {index}");
+  }
+}
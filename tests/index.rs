@@ -0,0 +1,241 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Parses every search filter, matches them against indexed entries, and
+//! reads indices back, including damaged ones
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+#![allow(clippy::cast_possible_truncation)]
+
+use {
+  para_cli::{
+    index::{self, Entry, Filter},
+    ppm::TMB_SIZE,
+  },
+  std::{fs, path::PathBuf},
+};
+
+fn temp_path(name: &str) -> PathBuf {
+  std::env::temp_dir().join(format!("para-index-{}-{name}", std::process::id()))
+}
+
+/// A PPM's entry, with every field filled in
+fn ppm_entry() -> Entry {
+  Entry {
+    path:                "collection/a.ppm".to_string(),
+    format:              "ppm".to_string(),
+    root_author_name:    "Root".to_string(),
+    root_author_id:      "0123456789ABCDEF".to_string(),
+    parent_author_name:  "Parent".to_string(),
+    parent_author_id:    "1111111111111111".to_string(),
+    current_author_name: "MrJohn".to_string(),
+    current_author_id:   "2222222222222222".to_string(),
+    parent_filename:     "F78DA8_14FC2C7DDEA4D_000".to_string(),
+    current_filename:    "F78DA8_14FC2C7DDEA4D_001".to_string(),
+    timestamp:           "2010-06-15T12:00:00+00:00".to_string(),
+    frame_count:         120,
+    frame_speed:         Some(5),
+    framerate:           Some(6.0),
+    duration:            Some(20.0),
+    lock:                true,
+    sound:               Some(true),
+    sha1:                "0a1b2c3d".to_string(),
+  }
+}
+
+/// A `.tmb`'s entry, which lacks the fields a thumbnail doesn't hold
+fn tmb_entry() -> Entry {
+  Entry {
+    format: "tmb".to_string(),
+    frame_speed: None,
+    framerate: None,
+    duration: None,
+    sound: None,
+    timestamp: String::new(),
+    ..ppm_entry()
+  }
+}
+
+fn matches(filter: &str, entry: &Entry) -> bool {
+  Filter::parse(filter).unwrap().matches(entry)
+}
+
+#[test]
+fn parse() {
+  for filter in [
+    "author=john",
+    "filename=F78DA8",
+    "after=2010-01-01",
+    "before=2010-12-31",
+    "framerate=0.5",
+    "min_frames=100",
+    "min_duration=1.5",
+    "lock=yes",
+    "lock=false",
+    "sound=no",
+    "sound=true",
+    "sha1=0A1B",
+  ] {
+    assert!(Filter::parse(filter).is_ok(), "{filter}");
+  }
+
+  for filter in [
+    "author",
+    "speed=5",
+    "=john",
+    "after=2010-13-01",
+    "before=yesterday",
+    "framerate=fast",
+    "min_frames=-1",
+    "min_frames=65536",
+    "min_duration=long",
+    "lock=maybe",
+    "sound=",
+  ] {
+    assert_eq!(
+      Filter::parse(filter).err().unwrap(),
+      format!("invalid filter({filter})")
+    );
+  }
+}
+
+#[test]
+fn filters() {
+  let entry = ppm_entry();
+
+  // Any author's name or ID, ignoring case
+  assert!(matches("author=mrjohn", &entry));
+  assert!(matches("author=root", &entry));
+  assert!(matches("author=abcdef", &entry));
+  assert!(!matches("author=someone", &entry));
+  assert!(matches("filename=14fc2c7ddea4d_000", &entry));
+  assert!(!matches("filename=_002", &entry));
+
+  // Dates are inclusive
+  assert!(matches("after=2010-06-15", &entry));
+  assert!(!matches("after=2010-06-16", &entry));
+  assert!(matches("before=2010-06-15", &entry));
+  assert!(!matches("before=2010-06-14", &entry));
+  assert!(matches("framerate=6", &entry));
+  assert!(!matches("framerate=5", &entry));
+  assert!(matches("min_frames=120", &entry));
+  assert!(!matches("min_frames=121", &entry));
+  assert!(matches("min_duration=20", &entry));
+  assert!(!matches("min_duration=20.5", &entry));
+  assert!(matches("lock=yes", &entry));
+  assert!(!matches("lock=no", &entry));
+  assert!(matches("sound=yes", &entry));
+  assert!(!matches("sound=no", &entry));
+  assert!(matches("sha1=0A1B", &entry));
+  assert!(!matches("sha1=1b2c", &entry));
+}
+
+#[test]
+fn missing_fields() {
+  let entry = tmb_entry();
+
+  // An entry without the field being filtered on doesn't match, either way
+  for filter in [
+    "after=2000-01-01",
+    "before=2100-01-01",
+    "framerate=6",
+    "min_duration=0",
+    "sound=yes",
+    "sound=no",
+  ] {
+    assert!(!matches(filter, &entry), "{filter}");
+  }
+
+  assert!(matches("min_frames=0", &entry));
+  assert!(matches("author=mrjohn", &entry));
+}
+
+#[test]
+fn read_entries() {
+  let ppm =
+    fs::read(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("ppms/mrjohn.ppm"))
+      .unwrap();
+  let ppm_path = temp_path("mrjohn.ppm");
+  let tmb_path = temp_path("mrjohn.tmb");
+
+  fs::write(&ppm_path, &ppm).unwrap();
+  fs::write(&tmb_path, &ppm[..TMB_SIZE as usize]).unwrap();
+
+  let flipnote = Entry::read(&ppm_path.to_string_lossy()).unwrap();
+  let tmb = Entry::read(&tmb_path.to_string_lossy()).unwrap();
+
+  fs::remove_file(&ppm_path).unwrap();
+  fs::remove_file(&tmb_path).unwrap();
+  assert_eq!(flipnote.format, "ppm");
+  assert!(flipnote.framerate.is_some());
+  assert!(flipnote.duration.is_some());
+  assert_eq!(tmb.format, "tmb");
+  assert_eq!(tmb.frame_count, flipnote.frame_count);
+  assert_eq!(tmb.current_filename, flipnote.current_filename);
+  assert_eq!(tmb.frame_speed, None);
+  assert_eq!(tmb.framerate, None);
+}
+
+#[test]
+fn round_trip() {
+  let path = temp_path("round_trip.jsonl");
+  let path = path.to_string_lossy();
+
+  index::write(&[ppm_entry(), tmb_entry()], &path).unwrap();
+
+  let entries = index::read(&path).unwrap();
+
+  fs::remove_file(&*path).unwrap();
+  assert_eq!(entries.len(), 2);
+  assert_eq!(
+    serde_json::to_value(&entries[0]).unwrap(),
+    serde_json::to_value(ppm_entry()).unwrap()
+  );
+  assert_eq!(
+    serde_json::to_value(&entries[1]).unwrap(),
+    serde_json::to_value(tmb_entry()).unwrap()
+  );
+}
+
+#[test]
+fn damaged_index() {
+  let path = temp_path("damaged.jsonl");
+  let path = path.to_string_lossy();
+  let line = serde_json::to_string(&ppm_entry()).unwrap();
+
+  // Blank lines are skipped
+  fs::write(&*path, format!("{line}\n\n{line}\n")).unwrap();
+  assert_eq!(index::read(&path).unwrap().len(), 2);
+
+  // Neither invalid UTF-8 nor invalid JSON cuts the index short silently
+  fs::write(
+    &*path,
+    [line.as_bytes(), b"\n\xFF\xFE\n", line.as_bytes()].concat(),
+  )
+  .unwrap();
+  assert!(
+    index::read(&path)
+      .err()
+      .unwrap()
+      .starts_with(&format!("could not read {path}: "))
+  );
+
+  fs::write(&*path, format!("{line}\n{{\n{line}\n")).unwrap();
+  assert!(
+    index::read(&path)
+      .err()
+      .unwrap()
+      .starts_with(&format!("invalid index({path}): "))
+  );
+
+  fs::remove_file(&*path).unwrap();
+  assert!(index::read(&path).is_err());
+}
@@ -0,0 +1,408 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Decodes KWZs built from known tiles, and checks that damaged ones load
+//! without panicking
+//!
+//! <https://github.com/Flipnote-Collective/flipnote-studio-3d-docs/wiki/kwz-format>
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+#![allow(clippy::cast_possible_truncation)]
+
+use para_cli::{
+  adpcm,
+  flipnote::{self, Flipnote},
+  kwz::KWZParser,
+  palette::Palette,
+};
+
+const WIDTH: usize = 320;
+const HEIGHT: usize = 240;
+const TILES: u32 = (WIDTH / 8 * HEIGHT / 8) as u32;
+
+/// White paper, black and red pens on layer one, and every layer new
+const NEW_FRAME: u32 = 0x0000_2170;
+/// The same colours, with every layer based on the previous frame
+const DIFF_FRAME: u32 = 0x0000_2100;
+
+/// Line table index of eight pixels of pen one
+const PEN_ONE_LINE: u32 = 3280;
+
+/// Layer data, written least significant bit first
+#[derive(Default)]
+struct Bits {
+  bytes: Vec<u8>,
+  value: u64,
+  count: u32,
+}
+impl Bits {
+  fn write(&mut self, count: u32, value: u32) -> &mut Self {
+    self.value |= u64::from(value) << self.count;
+    self.count += count;
+
+    while self.count >= 8 {
+      self.bytes.push(self.value as u8);
+      self.value >>= 8;
+      self.count -= 8;
+    }
+
+    self
+  }
+
+  /// Skip `tiles` tiles, up to 32 at a time
+  fn skip(&mut self, mut tiles: u32) -> &mut Self {
+    while tiles > 0 {
+      let skipped = tiles.min(32);
+
+      self.write(3, 5).write(5, skipped - 1);
+
+      tiles -= skipped;
+    }
+
+    self
+  }
+
+  /// Pad to whole 16-bit words
+  fn finish(&mut self) -> Vec<u8> {
+    if self.count > 0 {
+      self.write(8 - self.count, 0);
+    }
+
+    if self.bytes.len() % 2 == 1 {
+      self.bytes.push(0);
+    }
+
+    std::mem::take(&mut self.bytes)
+  }
+}
+
+/// A layer left unchanged from the previous frame
+fn unchanged() -> Vec<u8> { Bits::default().skip(TILES).finish() }
+
+fn section(magic: [u8; 4], data: &[u8]) -> Vec<u8> {
+  let mut section = magic.to_vec();
+
+  section.extend((data.len() as u32).to_le_bytes());
+  section.extend(data);
+
+  section
+}
+
+/// A KWZ of frames given as their flags and three layers, with a BGM track
+fn build_kwz(speed: u8, frames: &[(u32, [Vec<u8>; 3])]) -> Vec<u8> {
+  let mut header = vec![0; 4];
+  let wide = |name: &str| {
+    let mut bytes =
+      name.encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>();
+
+    bytes.resize(22, 0);
+
+    bytes
+  };
+
+  header.extend(1_000_u32.to_le_bytes());
+  header.extend(2_000_u32.to_le_bytes());
+  header.extend(0_u32.to_le_bytes());
+
+  for _ in 0..3 {
+    header.extend(0..10);
+  }
+
+  header.extend(wide("root"));
+  header.extend(wide("parent"));
+  header.extend(wide("current"));
+
+  for filename in ["root", "parent", "current"] {
+    let mut bytes = filename.as_bytes().to_vec();
+
+    bytes.resize(28, 0);
+    header.extend(bytes);
+  }
+
+  header.extend((frames.len() as u16).to_le_bytes());
+  header.extend(1_u16.to_le_bytes());
+  // Locked
+  header.extend(1_u16.to_le_bytes());
+  header.push(speed);
+  header.push(0);
+
+  let mut sound = vec![0; 4];
+
+  sound
+    .extend([4, 0, 0, 0, 0].iter().flat_map(|size: &u32| size.to_le_bytes()));
+  sound.extend([0; 4]);
+  sound.extend([0x12, 0x34, 0x56, 0x78]);
+
+  let mut data = vec![0; 4];
+  let mut meta = Vec::new();
+
+  for (flags, layers) in frames {
+    meta.extend(flags.to_le_bytes());
+
+    for layer in layers {
+      meta.extend((layer.len() as u16).to_le_bytes());
+      data.extend(layer);
+    }
+
+    meta.extend([0; 10]);
+    meta.extend([0; 3]);
+    meta.push(0);
+    meta.extend([0; 4]);
+  }
+
+  [
+    section(*b"KFH\x14", &header),
+    section(*b"KSN\x01", &sound),
+    section(*b"KMC\x02", &data),
+    section(*b"KMI\x05", &meta),
+  ]
+  .concat()
+}
+
+/// Two frames; the first with a tile of pen one on layer one and a tile of
+/// pen two on layer two beside it, and the second unchanged from the first
+fn fixture(speed: u8) -> Vec<u8> {
+  let layer_one = Bits::default()
+    .write(3, 1)
+    .write(13, PEN_ONE_LINE)
+    .skip(TILES - 1)
+    .finish();
+  // Common line two is every pixel of pen two
+  let layer_two =
+    Bits::default().skip(1).write(3, 0).write(5, 2).skip(TILES - 2).finish();
+
+  build_kwz(speed, &[
+    (NEW_FRAME, [layer_one, layer_two, unchanged()]),
+    (DIFF_FRAME, [unchanged(), unchanged(), unchanged()]),
+  ])
+}
+
+fn load(bytes: Vec<u8>) -> KWZParser {
+  let mut parser = KWZParser::new(bytes);
+
+  parser.load().unwrap();
+
+  parser
+}
+
+/// Every pixel of a frame, drawn with each layer's first pen as `pen`
+fn expected(pen: [u8; 2]) -> Vec<Vec<u8>> {
+  let mut pixels = vec![vec![0; WIDTH]; HEIGHT];
+
+  for line in &mut pixels[..8] {
+    line[..8].fill(pen[0]);
+    line[8..16].fill(pen[1]);
+  }
+
+  pixels
+}
+
+#[test]
+fn metadata() {
+  let parser = load(fixture(7));
+  let metadata = parser.metadata();
+
+  assert_eq!(parser.get_frame_count(), 2);
+  assert_eq!(parser.get_thumb_index(), 1);
+  assert!((parser.get_framerate() - 12.0).abs() < 1e-9);
+  assert!(metadata.lock);
+  assert_eq!(metadata.frame_speed, 7);
+  assert_eq!(metadata.current_filename, "current");
+  assert_eq!(metadata.parent_filename, "parent");
+  assert_eq!(metadata.authors[0].0, "root");
+  assert_eq!(
+    metadata.authors[2],
+    ("current".to_string(), "09080706050403020100".to_string())
+  );
+  assert_eq!(metadata.timestamp.timestamp(), 946_684_800 + 2_000);
+}
+
+#[test]
+fn frames() {
+  let mut parser = load(fixture(7));
+
+  assert_eq!(parser.get_frame_pixels(0), expected([1, 4]));
+  // Diff frames are based on the frame before them, however they're reached
+  assert_eq!(parser.get_frame_pixels(1), expected([1, 4]));
+  assert_eq!(load(fixture(7)).get_frame_pixels(1), expected([1, 4]));
+
+  let layers = parser.get_layer_pixels(0);
+
+  assert_eq!(layers[0][0][..16], [[1; 8], [0; 8]].concat());
+  assert_eq!(layers[1][7][..16], [[0; 8], [4; 8]].concat());
+  assert!(layers[2].iter().flatten().all(|&pixel| pixel == 0));
+
+  let palette = parser.get_frame_palette(0, &Palette::N3DS);
+
+  assert_eq!(palette[..3], [
+    Palette::N3DS.white,
+    Palette::N3DS.black,
+    Palette::N3DS.red
+  ]);
+}
+
+/// Samples are four bits wide until the step index falls below 18, after
+/// which they're two; the last two bits of a byte are always a sample
+#[test]
+fn adpcm() {
+  assert_eq!(adpcm::decode_kwz(&[0x00]), [672, 1280]);
+  assert_eq!(adpcm::decode_kwz(&[0x08]), [-672, -64]);
+  // Eleven bytes of 4-bit samples, one of three samples, then four of four
+  assert_eq!(adpcm::decode_kwz(&[0; 16]).len(), 22 + 3 + 4 * 4);
+
+  let samples =
+    adpcm::decode_kwz(&[[0; 11].as_slice(), &[0x93, 0x27, 0xE1]].concat());
+
+  assert_eq!(samples.len(), 29);
+  assert_eq!(samples[22..], [6896, 7552, 7472, 8688, 9568, 10048, 8160]);
+  // The 12-bit predictor saturates
+  assert_eq!(adpcm::decode_kwz(&[0x12, 0x34, 0x56, 0x78]), [
+    3360, 5184, 10192, 14896, 22864, 32752, 31168, 32752
+  ]);
+}
+
+#[test]
+fn sound_tracks() {
+  let bgm = [0x12, 0x34, 0x56, 0x78];
+  let parser = load(fixture(7));
+  let tracks = parser.sound_tracks();
+
+  assert_eq!(
+    tracks.iter().map(|track| (track.name, track.size)).collect::<Vec<_>>(),
+    [("bgm", 4), ("se1", 0), ("se2", 0), ("se3", 0), ("se4", 0)]
+  );
+  assert_eq!(parser.decode_sound_track(0), adpcm::decode_kwz(&bgm));
+  // Measured by their samples, rather than by their size
+  assert!((tracks[0].duration - 8.0 / 16364.0).abs() < 1e-12);
+
+  for index in 1..=5 {
+    assert!(parser.decode_sound_track(index).is_empty());
+  }
+
+  // A track claiming to run past the end of the file is cut short by it
+  let mut kwz = fixture(7);
+  let start = kwz.windows(4).position(|window| window == bgm).unwrap();
+  let size =
+    kwz.windows(4).position(|window| window == b"KSN\x01").unwrap() + 12;
+
+  kwz[size..size + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+  let expected = adpcm::decode_kwz(&kwz[start..]);
+
+  assert_eq!(load(kwz).decode_sound_track(0), expected);
+}
+
+/// Frames decode the same however they're reached, including seeking back
+/// to the first after decoding a later one
+#[test]
+fn seeking() {
+  // A second frame with more ink, based on the first
+  let layer_three = Bits::default()
+    .skip(2)
+    .write(3, 1)
+    .write(13, PEN_ONE_LINE)
+    .skip(TILES - 3)
+    .finish();
+  let kwz = build_kwz(7, &[
+    (NEW_FRAME, [unchanged(), unchanged(), unchanged()]),
+    (DIFF_FRAME, [unchanged(), unchanged(), layer_three]),
+    (NEW_FRAME, [unchanged(), unchanged(), unchanged()]),
+  ]);
+  let fresh = |index| load(kwz.clone()).get_frame_pixels(index);
+  let mut parser = load(kwz.clone());
+
+  assert_ne!(fresh(1), fresh(0));
+
+  for index in [1, 0, 1, 2, 0, 2, 1] {
+    assert_eq!(parser.get_frame_pixels(index), fresh(index), "frame {index}");
+  }
+}
+
+#[test]
+fn out_of_range_speed() {
+  // Plays at the slowest speed
+  for speed in [11, 200, 255] {
+    assert!((load(fixture(speed)).get_framerate() - 0.2).abs() < 1e-9);
+  }
+}
+
+#[test]
+fn out_of_range_lines() {
+  // Line indices past the 6561 lines of the line table draw nothing
+  let layer = Bits::default()
+    .write(3, 1)
+    .write(13, 8191)
+    .write(3, 3)
+    .write(13, 6561)
+    .write(3, 7)
+    .write(2, 0)
+    .write(1, 0)
+    .write(13, 7000)
+    .write(13, 8000)
+    .skip(TILES - 3)
+    .finish();
+  let mut parser =
+    load(build_kwz(7, &[(NEW_FRAME, [layer, unchanged(), unchanged()])]));
+
+  assert_eq!(parser.get_frame_pixels(0), vec![vec![0; WIDTH]; HEIGHT]);
+  assert_eq!(parser.get_frame_pixels(1), vec![vec![0; WIDTH]; HEIGHT]);
+}
+
+#[test]
+fn missing_sections() {
+  let kwz = fixture(7);
+
+  for magic in [b"KFH", b"KMC", b"KMI"] {
+    let start = kwz.windows(3).position(|window| window == magic).unwrap();
+    let mut damaged = kwz.clone();
+
+    damaged[start + 2] = b'X';
+
+    let mut parser = KWZParser::new(damaged.clone());
+
+    assert_eq!(
+      parser.load(),
+      Err(format!("missing {} section", String::from_utf8_lossy(magic)))
+    );
+    assert!(flipnote::from_bytes(damaged).is_none());
+  }
+}
+
+#[test]
+fn truncated() {
+  let kwz = fixture(7);
+
+  for length in 0..kwz.len() {
+    let Some(mut parser) = flipnote::from_bytes(kwz[..length].to_vec()) else {
+      continue;
+    };
+    let frame_count = usize::from(parser.get_frame_count());
+
+    for index in 0..=frame_count {
+      parser.get_frame_pixels(index);
+      parser.get_frame_palette(index, &Palette::N3DS);
+    }
+
+    parser.dump_json(true);
+    parser.info_json();
+  }
+}
+
+#[test]
+fn oversized_frame_meta() {
+  let mut kwz = fixture(7);
+  let start = kwz.windows(4).position(|window| window == b"KMI\x05").unwrap();
+
+  // Claims far more frames than the file holds
+  kwz[start + 4..start + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+
+  assert_eq!(load(kwz).get_frame_count(), 2);
+}
@@ -0,0 +1,225 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Builds the derivation tree of a collection of `.tmb` files edited from
+//! one another, and checks its Graphviz and JSON output
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+
+use {
+  para_cli::lineage::Lineage,
+  std::{fs, path::PathBuf},
+};
+
+/// An author's name, and the byte their ID is made of
+type Author = (&'static str, u8);
+
+const ALICE: Author = ("Alice", 0x11);
+const BOB: Author = ("Bob", 0x22);
+const CAROL: Author = ("Ca\"rol", 0x33);
+
+/// Lay a filename formatted as `<MAC>_<random>_<edits>` out as the eighteen
+/// bytes it's stored as
+fn stored_filename(name: &str) -> Vec<u8> {
+  let mut bytes = (0..3)
+    .map(|index| {
+      u8::from_str_radix(&name[index * 2..index * 2 + 2], 16).unwrap()
+    })
+    .collect::<Vec<_>>();
+
+  bytes.extend(&name.as_bytes()[7..20]);
+  bytes.extend(name[21..].parse::<u16>().unwrap().to_le_bytes());
+
+  bytes
+}
+
+/// The header and metadata of a `.tmb`, edited by `current` from `parent`,
+/// which `root` first drew
+fn build_tmb(
+  [root, parent, current]: [Author; 3],
+  parent_filename: &str,
+  current_filename: &str,
+) -> Vec<u8> {
+  let mut tmb = vec![0; 0x06A0];
+  let name = |(name, _): Author| {
+    let mut bytes =
+      name.encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>();
+
+    bytes.resize(22, 0);
+
+    bytes
+  };
+  let id = |(_, id): Author| [id; 8];
+
+  tmb[..4].copy_from_slice(b"PARA");
+  tmb[0x14..0x2A].copy_from_slice(&name(root));
+  tmb[0x2A..0x40].copy_from_slice(&name(parent));
+  tmb[0x40..0x56].copy_from_slice(&name(current));
+  tmb[0x56..0x5E].copy_from_slice(&id(parent));
+  tmb[0x5E..0x66].copy_from_slice(&id(current));
+  tmb[0x66..0x78].copy_from_slice(&stored_filename(parent_filename));
+  tmb[0x78..0x8A].copy_from_slice(&stored_filename(current_filename));
+  tmb[0x8A..0x92].copy_from_slice(&id(root));
+
+  tmb
+}
+
+/// A collection of an original, two edits of it, an edit of one of those
+/// held twice, and an edit of a Flipnote the collection doesn't hold
+fn collection() -> (PathBuf, Lineage) {
+  let root =
+    std::env::temp_dir().join(format!("para-lineage-{}", std::process::id()));
+  let nested = root.join("nested");
+  let original = "AABBCC_0123456789ABC_000";
+  let first_edit = "AABBCC_0123456789ABC_001";
+  let second_edit = "DDEEFF_0123456789ABC_001";
+  let missing = "AABBCC_FEDCBA9876543_004";
+
+  let _ = fs::remove_dir_all(&root);
+  fs::create_dir_all(&nested).unwrap();
+
+  for (path, tmb) in [
+    ("a.tmb", build_tmb([ALICE; 3], original, original)),
+    ("b.tmb", build_tmb([ALICE, ALICE, BOB], original, first_edit)),
+    ("c.tmb", build_tmb([ALICE, ALICE, CAROL], original, second_edit)),
+    (
+      "d.tmb",
+      build_tmb([ALICE, BOB, CAROL], first_edit, "AABBCC_0123456789ABC_002"),
+    ),
+    (
+      "nested/d.tmb",
+      build_tmb([ALICE, BOB, CAROL], first_edit, "AABBCC_0123456789ABC_002"),
+    ),
+    (
+      "e.tmb",
+      build_tmb([BOB, CAROL, ALICE], missing, "AABBCC_FEDCBA9876543_005"),
+    ),
+    ("notes.txt", b"not a Flipnote".to_vec()),
+  ] {
+    fs::write(root.join(path), tmb).unwrap();
+  }
+
+  let lineage = Lineage::from_directory(&root);
+
+  fs::remove_dir_all(&root).unwrap();
+
+  (root, lineage)
+}
+
+#[test]
+fn json() {
+  let (root, lineage) = collection();
+  let path = |name: &str| root.join(name).to_string_lossy().to_string();
+  let node = |filename: &str, (name, id): Author, root_author: Author| {
+    serde_json::json!({
+      "filename": filename,
+      "edits": filename[21..].parse::<u16>().unwrap(),
+      "author_name": name,
+      "author_id": format!("{id:02X}").repeat(8),
+      "root_author_name": root_author.0,
+      "root_author_id": format!("{:02X}", root_author.1).repeat(8),
+    })
+  };
+  let json = lineage.to_json();
+  let nodes = json["nodes"].as_array().unwrap();
+
+  // Nodes are ordered by filename
+  assert_eq!(
+    nodes
+      .iter()
+      .map(|node| node["filename"].as_str().unwrap())
+      .collect::<Vec<_>>(),
+    [
+      "AABBCC_0123456789ABC_000",
+      "AABBCC_0123456789ABC_001",
+      "AABBCC_0123456789ABC_002",
+      "AABBCC_FEDCBA9876543_004",
+      "AABBCC_FEDCBA9876543_005",
+      "DDEEFF_0123456789ABC_001",
+    ]
+  );
+
+  for (node, (expected, paths)) in nodes.iter().zip([
+    (node("AABBCC_0123456789ABC_000", ALICE, ALICE), vec![path("a.tmb")]),
+    (node("AABBCC_0123456789ABC_001", BOB, ALICE), vec![path("b.tmb")]),
+    (node("AABBCC_0123456789ABC_002", CAROL, ALICE), vec![
+      path("d.tmb"),
+      path("nested/d.tmb"),
+    ]),
+    // Only known as the parent of another, whose metadata names its author
+    (node("AABBCC_FEDCBA9876543_004", CAROL, BOB), vec![]),
+    (node("AABBCC_FEDCBA9876543_005", ALICE, BOB), vec![path("e.tmb")]),
+    (node("DDEEFF_0123456789ABC_001", CAROL, ALICE), vec![path("c.tmb")]),
+  ]) {
+    for (key, value) in expected.as_object().unwrap() {
+      assert_eq!(&node[key], value, "{} {key}", node["filename"]);
+    }
+
+    assert_eq!(node["present"], !paths.is_empty());
+    assert_eq!(node["paths"], serde_json::json!(paths));
+  }
+
+  assert_eq!(
+    json["edges"],
+    serde_json::json!([
+      {
+        "parent": "AABBCC_0123456789ABC_000",
+        "child": "AABBCC_0123456789ABC_001",
+      },
+      {
+        "parent": "AABBCC_0123456789ABC_000",
+        "child": "DDEEFF_0123456789ABC_001",
+      },
+      {
+        "parent": "AABBCC_0123456789ABC_001",
+        "child": "AABBCC_0123456789ABC_002",
+      },
+      {
+        "parent": "AABBCC_FEDCBA9876543_004",
+        "child": "AABBCC_FEDCBA9876543_005",
+      },
+    ])
+  );
+  assert_eq!(
+    json["roots"],
+    serde_json::json!(["AABBCC_0123456789ABC_000", "AABBCC_FEDCBA9876543_004"])
+  );
+}
+
+#[test]
+fn dot() {
+  let (_, lineage) = collection();
+
+  assert_eq!(
+    lineage.to_dot(),
+    "digraph lineage {
+  rankdir=LR;
+  node [shape=box, fontname=monospace];
+  \"AABBCC_0123456789ABC_000\" [label=\"AABBCC_0123456789ABC_000\\nAlice \
+     (1111111111111111)\\nedits 0\"];
+  \"AABBCC_0123456789ABC_001\" [label=\"AABBCC_0123456789ABC_001\\nBob \
+     (2222222222222222)\\nedits 1\"];
+  \"AABBCC_0123456789ABC_002\" [label=\"AABBCC_0123456789ABC_002\\nCa\\\"rol \
+     (3333333333333333)\\nedits 2\"];
+  \"AABBCC_FEDCBA9876543_004\" [label=\"AABBCC_FEDCBA9876543_004\\nCa\\\"rol \
+     (3333333333333333)\\nedits 4\", style=dashed];
+  \"AABBCC_FEDCBA9876543_005\" [label=\"AABBCC_FEDCBA9876543_005\\nAlice \
+     (1111111111111111)\\nedits 5\"];
+  \"DDEEFF_0123456789ABC_001\" [label=\"DDEEFF_0123456789ABC_001\\nCa\\\"rol \
+     (3333333333333333)\\nedits 1\"];
+  \"AABBCC_0123456789ABC_000\" -> \"AABBCC_0123456789ABC_001\";
+  \"AABBCC_0123456789ABC_000\" -> \"DDEEFF_0123456789ABC_001\";
+  \"AABBCC_0123456789ABC_001\" -> \"AABBCC_0123456789ABC_002\";
+  \"AABBCC_FEDCBA9876543_004\" -> \"AABBCC_FEDCBA9876543_005\";
+}
+"
+  );
+}
@@ -0,0 +1,113 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Decodes `.npf` and `.nbf` images built from known pixels, and checks that
+//! truncated images fail to load rather than panicking
+//!
+//! <https://github.com/pbsds/hatena-server/wiki/NPF-format>
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+#![allow(clippy::cast_possible_truncation)]
+
+use para_cli::npf::NPFParser;
+
+const TRANSPARENT: u16 = 0x0000;
+const WHITE: u16 = 0x7FFF;
+const RED: u16 = 0x001F;
+const BLUE: u16 = 0x7C00;
+
+fn build_image(palette: &[u16], data: &[u8]) -> Vec<u8> {
+  let mut image = b"UGAR".to_vec();
+
+  image.extend(2_u32.to_le_bytes());
+  image.extend((palette.len() as u32 * 2).to_le_bytes());
+  image.extend((data.len() as u32).to_le_bytes());
+  image.extend(palette.iter().flat_map(|colour| colour.to_le_bytes()));
+  image.extend(data);
+
+  image
+}
+
+/// A 4×4 `.npf` icon whose rows cycle through its palette
+fn build_npf() -> Vec<u8> {
+  // Two pixels per byte, the leftmost in the low nibble
+  build_image(&[TRANSPARENT, WHITE, RED, BLUE], &[0x21, 0x03].repeat(4))
+}
+
+fn load(bytes: Vec<u8>, bits_per_pixel: u8) -> NPFParser {
+  let mut parser = NPFParser::new(bytes, bits_per_pixel);
+
+  parser.load().unwrap();
+
+  parser
+}
+
+#[test]
+fn npf() {
+  let parser = load(build_npf(), 4);
+  let frame = parser.get_frame(None);
+
+  assert!(parser.is_transparent());
+  assert_eq!(frame.pixels, vec![vec![1, 2, 3, 0]; 4]);
+  assert_eq!(frame.colours, [
+    (0, 0, 0),
+    (255, 255, 255),
+    (255, 0, 0),
+    (0, 0, 255)
+  ]);
+
+  // An explicit width overrides the guess
+  let row = [1, 2, 3, 0].repeat(2);
+
+  assert_eq!(parser.get_frame(Some(8)).pixels, [row.clone(), row]);
+}
+
+#[test]
+fn nbf() {
+  // Two rows spanning the width of the screen, with indices past the palette
+  let data = (0..512_usize).map(|index| (index % 4) as u8).collect::<Vec<_>>();
+  let parser = load(build_image(&[WHITE, RED], &data), 8);
+  let frame = parser.get_frame(None);
+
+  assert!(!parser.is_transparent());
+  assert_eq!(frame.pixels.len(), 2);
+  assert_eq!(frame.pixels[0], data[..256]);
+
+  // Indices past the palette are drawn with its last colour
+  assert_eq!(frame.colours, [
+    (255, 255, 255),
+    (255, 0, 0),
+    (255, 0, 0),
+    (255, 0, 0)
+  ]);
+}
+
+#[test]
+fn truncated() {
+  let npf = build_npf();
+
+  for length in 0..npf.len() {
+    assert!(NPFParser::new(npf[..length].to_vec(), 4).load().is_err());
+  }
+}
+
+#[test]
+fn oversized_image() {
+  let mut npf = build_npf();
+
+  // Claims far more pixels than the image holds
+  npf[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+
+  assert_eq!(
+    NPFParser::new(npf, 4).load().err().unwrap(),
+    format!("truncated image data: expected {} bytes, found 8", u32::MAX)
+  );
+}
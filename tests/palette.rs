@@ -0,0 +1,120 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Parses palettes from presets, hexadecimal colours, and files, including
+//! the errors each reports
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+
+use {para_cli::palette::Palette, std::fs};
+
+const FOUR_COLOURS: &str = "invalid palette({}), expected four colours: \
+                            black, white, red, blue, optionally followed by \
+                            yellow and green";
+
+fn expected_four(values: &str) -> Result<Palette, String> {
+  Err(FOUR_COLOURS.replace("{}", values))
+}
+
+#[test]
+fn presets() {
+  for (name, palette) in Palette::PRESETS {
+    assert_eq!(Palette::from_preset(name), Some(palette));
+    assert_eq!(Palette::from_preset(&name.to_uppercase()), Some(palette));
+    assert_eq!(Palette::parse(name), Ok(palette));
+  }
+
+  assert_eq!(Palette::from_preset("sepia"), None);
+  assert_eq!(Palette::default(), Palette::DSI);
+}
+
+#[test]
+fn from_hex() {
+  // Yellow and green default to those of `dsi`
+  let four = Palette {
+    yellow: Palette::DSI.yellow,
+    green: Palette::DSI.green,
+    ..Palette::HATENA
+  };
+
+  assert_eq!(Palette::from_hex("#000000,#FFFFFF,#FF0000,#0000FF"), Ok(four));
+  // Separated by whitespace, without `#`, and in lowercase
+  assert_eq!(Palette::from_hex("000000 ffffff\tff0000\n0000ff,"), Ok(four));
+  assert_eq!(
+    Palette::from_hex("000000,FFFFFF,FF0000,0000FF,FFFF00,008000"),
+    Ok(Palette::HATENA)
+  );
+}
+
+#[test]
+fn from_hex_errors() {
+  for values in [
+    "",
+    "000000,FFFFFF,FF0000",
+    "000000,FFFFFF,FF0000,0000FF,FFFF00",
+    "000000,FFFFFF,FF0000,0000FF,FFFF00,008000,000000",
+  ] {
+    assert_eq!(Palette::from_hex(values), expected_four(values));
+  }
+
+  assert_eq!(
+    Palette::from_hex("000000,FFFFFF,FF0000,00F"),
+    Err("invalid colour(00F), expected RRGGBB".to_string())
+  );
+  assert_eq!(
+    Palette::from_hex("000000,FFFFFF,#GG0000,0000FF"),
+    Err("invalid colour(#GG0000)".to_string())
+  );
+  // Six bytes, but not six digits
+  assert_eq!(
+    Palette::from_hex("000000,FFFFFF,FF00é,0000FF"),
+    Err("invalid colour(FF00é)".to_string())
+  );
+}
+
+#[test]
+fn from_file() {
+  let path = std::env::temp_dir()
+    .join(format!("para-palette-{}.txt", std::process::id()));
+
+  fs::write(&path, "#000000\n#FFFFFF\n#FF0000\n#0000FF\n#FFFF00\n#008000\n")
+    .unwrap();
+
+  let palette = Palette::from_file(&path);
+  let parsed = Palette::parse(path.to_str().unwrap());
+
+  fs::write(&path, "#000000\n#FFFFFF\n").unwrap();
+
+  let too_few = Palette::from_file(&path);
+
+  fs::remove_file(&path).unwrap();
+  assert_eq!(palette, Ok(Palette::HATENA));
+  assert_eq!(parsed, Ok(Palette::HATENA));
+  assert_eq!(too_few, expected_four("#000000\n#FFFFFF\n"));
+
+  let error = Palette::from_file(&path).unwrap_err();
+
+  assert!(
+    error.starts_with(&format!("could not read {}: ", path.display())),
+    "{error}"
+  );
+}
+
+#[test]
+fn parse_errors() {
+  let error = Palette::parse("sepia").unwrap_err();
+
+  assert_eq!(
+    error,
+    "invalid colour(sepia), expected RRGGBB\navailable palette presets: dsi, \
+     3ds, hatena, high-contrast, greyscale"
+  );
+}
@@ -0,0 +1,109 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Upscales small bitmaps with each scaling method, comparing them with
+//! results worked out by hand from the Scale2x and Scale3x rules
+//!
+//! <https://www.scale2x.it/algorithm>
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+
+use para_cli::scale::Scaling;
+
+fn bitmap<const W: usize>(lines: &[[u8; W]]) -> Vec<Vec<u8>> {
+  lines.iter().map(|line| line.to_vec()).collect()
+}
+
+/// A diagonal line, which Scale2x and Scale3x smooth into a staircase
+fn diagonal() -> Vec<Vec<u8>> { bitmap(&[[1, 0], [0, 1]]) }
+
+/// A lone pixel, which neither kernel rounds off
+fn lone_pixel() -> Vec<Vec<u8>> { bitmap(&[[0, 0, 0], [0, 2, 0], [0, 0, 0]]) }
+
+#[test]
+fn options() {
+  assert_eq!(Scaling::from_options("1", false), Ok(Scaling::Nearest(1)));
+  assert_eq!(Scaling::from_options("3", false), Ok(Scaling::Nearest(3)));
+  assert_eq!(Scaling::from_options("4", true), Ok(Scaling::Smooth(4)));
+  assert_eq!(
+    Scaling::from_options("5", true),
+    Err("invalid scale factor(5), expected 1..4".to_string())
+  );
+  assert_eq!(
+    Scaling::from_options("0", false),
+    Err("invalid scale factor(0), expected 1..4".to_string())
+  );
+  assert_eq!(
+    Scaling::from_options("two", false),
+    Err("invalid scale factor(two)".to_string())
+  );
+}
+
+#[test]
+fn nearest() {
+  assert_eq!(
+    Scaling::Nearest(2).apply(diagonal()),
+    bitmap(&[[1, 1, 0, 0], [1, 1, 0, 0], [0, 0, 1, 1], [0, 0, 1, 1]])
+  );
+  assert_eq!(Scaling::Nearest(1).apply(lone_pixel()), lone_pixel());
+  assert_eq!(Scaling::Smooth(1).apply(diagonal()), diagonal());
+}
+
+#[test]
+fn scale2x() {
+  assert_eq!(
+    Scaling::Smooth(2).apply(diagonal()),
+    bitmap(&[[1, 1, 0, 0], [1, 0, 1, 0], [0, 1, 0, 1], [0, 0, 1, 1]])
+  );
+  assert_eq!(
+    Scaling::Smooth(2).apply(lone_pixel()),
+    Scaling::Nearest(2).apply(lone_pixel())
+  );
+}
+
+#[test]
+fn scale3x() {
+  assert_eq!(
+    Scaling::Smooth(3).apply(diagonal()),
+    bitmap(&[
+      [1, 1, 1, 0, 0, 0],
+      [1, 1, 0, 1, 0, 0],
+      [1, 0, 0, 1, 1, 0],
+      [0, 1, 1, 0, 0, 1],
+      [0, 0, 1, 0, 1, 1],
+      [0, 0, 0, 1, 1, 1],
+    ])
+  );
+  assert_eq!(
+    Scaling::Smooth(3).apply(lone_pixel()),
+    Scaling::Nearest(3).apply(lone_pixel())
+  );
+}
+
+#[test]
+fn scale4x() {
+  let diagonal = diagonal();
+
+  // Scale4x is Scale2x applied twice
+  assert_eq!(
+    Scaling::Smooth(4).apply(diagonal.clone()),
+    Scaling::Smooth(2).apply(Scaling::Smooth(2).apply(diagonal))
+  );
+
+  // The second pass rounds off the corners of the block the first leaves
+  let mut rounded = Scaling::Nearest(4).apply(lone_pixel());
+
+  for (x, y) in [(4, 4), (7, 4), (4, 7), (7, 7)] {
+    rounded[y][x] = 0;
+  }
+
+  assert_eq!(Scaling::Smooth(4).apply(lone_pixel()), rounded);
+}
@@ -0,0 +1,708 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Decodes PPMs built from known bitmaps, covering every line type,
+//! translated diff frames, frame colours, and every speed, recovers damaged
+//! ones, and groups duplicates of them
+//!
+//! <https://github.com/pbsds/hatena-server/wiki/PPM-format>
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+#![allow(clippy::cast_possible_truncation)]
+
+use para_cli::{
+  adpcm, check, dedupe, diff,
+  flipnote::{self, Flipnote},
+  palette::Palette,
+  ppm::{self, PPMParser, framerates},
+  signature::SIGNATURE_SIZE,
+};
+
+const WIDTH: usize = 256;
+const HEIGHT: usize = 192;
+
+type Bitmap = Vec<Vec<u8>>;
+
+/// A frame header; white paper, with black ink on layer one and red on layer
+/// two
+const NEW_FRAME: u8 = 0b1001_0011;
+const DIFF_FRAME: u8 = 0b0001_0011;
+/// Set alongside the translation flags when a frame is translated
+const TRANSLATED: u8 = 0b0110_0000;
+
+fn blank() -> Bitmap { vec![vec![0; WIDTH]; HEIGHT] }
+
+/// A bitmap with ink scattered over all of it, but none in the left half of
+/// its even lines, so that compressed lines skip chunks
+fn pattern(seed: usize) -> Bitmap {
+  (0..HEIGHT)
+    .map(|y| {
+      (0..WIDTH)
+        .map(|x| {
+          u8::from(
+            (y % 2 == 1 || x >= WIDTH / 2) && (x * 7 + y * 13 + seed) % 5 < 2,
+          )
+        })
+        .collect()
+    })
+    .collect()
+}
+
+fn inverted(bitmap: &Bitmap) -> Bitmap {
+  bitmap
+    .iter()
+    .map(|line| line.iter().map(|&pixel| pixel ^ 1).collect())
+    .collect()
+}
+
+/// Pack a line into 32 chunks of eight pixels, the leftmost in the lowest bit
+fn chunks(line: &[u8]) -> Vec<u8> {
+  line
+    .chunks_exact(8)
+    .map(|pixels| {
+      pixels.iter().rev().fold(0, |chunk, &pixel| (chunk << 1) | pixel)
+    })
+    .collect()
+}
+
+/// Encode a line as a line type; `0` has no data, `1` only the chunks which
+/// hold ink, `2` only the chunks which aren't full of ink, and `3` every chunk
+fn encode_line(line_type: u8, line: &[u8]) -> Vec<u8> {
+  let chunks = chunks(line);
+  let skipped = match line_type {
+    0 => return Vec::new(),
+    1 => 0x00,
+    2 => 0xFF,
+    _ => return chunks,
+  };
+  let usage = chunks
+    .iter()
+    .fold(0u32, |usage, &chunk| (usage << 1) | u32::from(chunk != skipped));
+
+  usage
+    .to_be_bytes()
+    .into_iter()
+    .chain(chunks.into_iter().filter(|&chunk| chunk != skipped))
+    .collect()
+}
+
+/// Encode a frame whose lines use the given line type in each layer
+fn encode_frame(
+  header: u8,
+  translation: (i8, i8),
+  layers: [(&Bitmap, &dyn Fn(usize) -> u8); 2],
+) -> Vec<u8> {
+  let mut frame = vec![header];
+
+  if header & TRANSLATED != 0 {
+    frame.extend(translation.0.to_le_bytes());
+    frame.extend(translation.1.to_le_bytes());
+  }
+
+  for (_, line_type) in layers {
+    frame.extend((0..HEIGHT / 4).map(|line| {
+      (0..4).fold(0, |types, offset| {
+        types | (line_type(line * 4 + offset) << (offset * 2))
+      })
+    }));
+  }
+
+  for (bitmap, line_type) in layers {
+    for (index, line) in bitmap.iter().enumerate() {
+      frame.extend(encode_line(line_type(index), line));
+    }
+  }
+
+  frame
+}
+
+/// Lay frames out as a PPM with the given speed, but without sound data or
+/// a signature
+fn build_ppm(frames: &[Vec<u8>], speed: u8) -> Vec<u8> {
+  let table_size = frames.len() * 4;
+  let animation_size =
+    8 + table_size + frames.iter().map(Vec::len).sum::<usize>();
+  let mut ppm = vec![0; 0x06A0];
+
+  ppm[..4].copy_from_slice(b"PARA");
+  ppm[0x04..0x08].copy_from_slice(&(animation_size as u32).to_le_bytes());
+  ppm[0x0C..0x0E].copy_from_slice(&(frames.len() as u16 - 1).to_le_bytes());
+  ppm.extend((table_size as u16).to_le_bytes());
+  ppm.extend([0; 6]);
+
+  let mut offset = 0;
+
+  for frame in frames {
+    ppm.extend((offset as u32).to_le_bytes());
+    offset += frame.len();
+  }
+
+  frames.iter().for_each(|frame| ppm.extend(frame));
+  // Sound effect flags
+  ppm.extend(vec![0; frames.len()]);
+
+  // The sound header starts at the next multiple of four
+  ppm.resize(ppm.len().next_multiple_of(4), 0);
+
+  // Sound track sizes, followed by the frame and BGM speeds
+  ppm.extend([0; 16]);
+  ppm.extend([8 - speed, 8 - speed]);
+  ppm.extend([0; 14]);
+
+  ppm
+}
+
+/// Lay frames out as a PPM as [`build_ppm`] does, followed by sound tracks
+/// and a stand-in signature
+fn build_signed_ppm(
+  frames: &[Vec<u8>],
+  speed: u8,
+  tracks: [&[u8]; 4],
+) -> Vec<u8> {
+  let mut ppm = build_ppm(frames, speed);
+  let sound_header = ppm.len() - 0x20;
+  let sound_size = tracks.iter().map(|track| track.len()).sum::<usize>();
+
+  ppm[0x08..0x0C].copy_from_slice(&(sound_size as u32).to_le_bytes());
+
+  for (index, track) in tracks.iter().enumerate() {
+    ppm[sound_header + index * 4..sound_header + index * 4 + 4]
+      .copy_from_slice(&(track.len() as u32).to_le_bytes());
+  }
+
+  tracks.iter().for_each(|track| ppm.extend(*track));
+  ppm.extend([0xA5; SIGNATURE_SIZE]);
+  ppm.extend([0; 0x10]);
+
+  ppm
+}
+
+/// A frame followed by `padding` bytes the decoder never reads, which moves
+/// where the animation data ends
+fn padded_frame(padding: usize) -> Vec<u8> {
+  let empty = |_| 0;
+  let mut frame =
+    encode_frame(NEW_FRAME, (0, 0), [(&blank(), &empty), (&blank(), &empty)]);
+
+  frame.extend(vec![0; padding]);
+
+  frame
+}
+
+fn load(ppm: Vec<u8>) -> PPMParser {
+  let mut parser = PPMParser::new(ppm);
+
+  parser.load();
+
+  parser
+}
+
+/// Move a bitmap, clearing whatever it's moved away from
+fn translate(bitmap: &Bitmap, (x, y): (i8, i8)) -> Bitmap {
+  let mut translated = blank();
+
+  for (target_y, line) in translated.iter_mut().enumerate() {
+    for (target_x, pixel) in line.iter_mut().enumerate() {
+      if let (Some(source_y), Some(source_x)) = (
+        target_y.checked_add_signed(-isize::from(y)),
+        target_x.checked_add_signed(-isize::from(x)),
+      ) && source_y < HEIGHT
+        && source_x < WIDTH
+      {
+        *pixel = bitmap[source_y][source_x];
+      }
+    }
+  }
+
+  translated
+}
+
+fn xor(first: &Bitmap, second: &Bitmap) -> Bitmap {
+  first
+    .iter()
+    .zip(second)
+    .map(|(first, second)| {
+      first.iter().zip(second).map(|(&a, &b)| a ^ b).collect()
+    })
+    .collect()
+}
+
+/// Layer pixels use the layer's palette index for ink
+fn layer_pixels(bitmap: &Bitmap, colour: u8) -> Bitmap {
+  bitmap
+    .iter()
+    .map(|line| line.iter().map(|&pixel| pixel * colour).collect())
+    .collect()
+}
+
+#[test]
+fn line_types() {
+  for line_type in 0..4 {
+    // Lines of type 0 hold no ink, and those of type 2 start off full of it
+    let (first, second) = match line_type {
+      0 => (blank(), blank()),
+      2 => (inverted(&pattern(0)), inverted(&pattern(1))),
+      _ => (pattern(0), pattern(1)),
+    };
+    let mut parser = load(build_ppm(
+      &[encode_frame(NEW_FRAME, (0, 0), [
+        (&first, &|_| line_type),
+        (&second, &|_| line_type),
+      ])],
+      8,
+    ));
+    let info = parser.get_frame_info(0);
+
+    assert_eq!(
+      parser.get_layer_pixels(0),
+      [layer_pixels(&first, 1), layer_pixels(&second, 2)],
+      "line type {line_type}"
+    );
+
+    for layer in info.line_types {
+      assert_eq!(layer[usize::from(line_type)], 192, "line type {line_type}");
+    }
+  }
+}
+
+/// Every line type in one frame, each layer cycling through them in a
+/// different order
+#[test]
+fn mixed_line_types() {
+  let first_type = |line: usize| (line % 4) as u8;
+  let second_type = |line: usize| 3 - (line % 4) as u8;
+  let for_line_types = |bitmap: Bitmap, line_type: &dyn Fn(usize) -> u8| {
+    bitmap
+      .into_iter()
+      .enumerate()
+      .map(|(index, line)| match line_type(index) {
+        0 => vec![0; WIDTH],
+        2 => line.iter().map(|&pixel| pixel ^ 1).collect(),
+        _ => line,
+      })
+      .collect::<Bitmap>()
+  };
+  let first = for_line_types(pattern(2), &first_type);
+  let second = for_line_types(pattern(3), &second_type);
+  let mut parser = load(build_ppm(
+    &[encode_frame(NEW_FRAME, (0, 0), [
+      (&first, &first_type),
+      (&second, &second_type),
+    ])],
+    8,
+  ));
+
+  assert_eq!(parser.get_frame_info(0).line_types, [[48; 4]; 2]);
+  assert_eq!(parser.get_layer_pixels(0), [
+    layer_pixels(&first, 1),
+    layer_pixels(&second, 2)
+  ]);
+
+  // Layer one is drawn over layer two
+  let pixels = parser.get_frame_pixels(0);
+
+  for (y, line) in pixels.iter().enumerate() {
+    for (x, &pixel) in line.iter().enumerate() {
+      let expected = match (first[y][x], second[y][x]) {
+        (1, _) => 1,
+        (_, 1) => 2,
+        _ => 0,
+      };
+
+      assert_eq!(pixel, expected, "pixel ({x}, {y})");
+    }
+  }
+}
+
+/// Diff frames are XOR-ed onto the frame before them, moved by their
+/// translation
+#[test]
+fn translated_diff_frames() {
+  let raw = |_| 3;
+  let empty = |_| 0;
+  let base = [pattern(4), pattern(5)];
+  let change = [pattern(6), pattern(7)];
+
+  for translation in [(0, 0), (3, 5), (-7, 2), (0, -40), (62, 1), (-128, 127)] {
+    let header =
+      if translation == (0, 0) { DIFF_FRAME } else { DIFF_FRAME | TRANSLATED };
+    let mut parser = load(build_ppm(
+      &[
+        encode_frame(NEW_FRAME, (0, 0), [(&base[0], &raw), (&base[1], &raw)]),
+        // Moves the first frame without changing it
+        encode_frame(header, translation, [
+          (&blank(), &empty),
+          (&blank(), &empty),
+        ]),
+        // Moves the first frame and draws over it
+        encode_frame(header, translation, [
+          (&change[0], &raw),
+          (&change[1], &raw),
+        ]),
+      ],
+      8,
+    ));
+    let moved =
+      [translate(&base[0], translation), translate(&base[1], translation)];
+    let moved_twice =
+      [translate(&moved[0], translation), translate(&moved[1], translation)];
+
+    assert_eq!(
+      parser.get_layer_pixels(1),
+      [layer_pixels(&moved[0], 1), layer_pixels(&moved[1], 2)],
+      "translation {translation:?}"
+    );
+    assert_eq!(
+      parser.get_layer_pixels(2),
+      [
+        layer_pixels(&xor(&moved_twice[0], &change[0]), 1),
+        layer_pixels(&xor(&moved_twice[1], &change[1]), 2)
+      ],
+      "translation {translation:?}"
+    );
+
+    let info = parser.get_frame_info(2);
+
+    assert!(!info.is_new_frame);
+    assert_eq!((info.translation_x, info.translation_y), translation);
+  }
+}
+
+#[test]
+fn frame_colours() {
+  let palette = Palette::DSI;
+  let empty = |_| 0;
+
+  for (header, colours) in [
+    (0b1000_0011, [palette.white, palette.black, palette.black]),
+    (0b1000_0010, [palette.black, palette.white, palette.white]),
+    (0b1001_0101, [palette.white, palette.red, palette.red]),
+    (0b1001_1111, [palette.white, palette.blue, palette.blue]),
+    (0b1001_1100, [palette.black, palette.red, palette.blue]),
+  ] {
+    let mut parser = load(build_ppm(
+      &[encode_frame(header, (0, 0), [(&blank(), &empty), (&blank(), &empty)])],
+      8,
+    ));
+
+    assert_eq!(
+      parser.get_frame_palette(0, &palette),
+      colours,
+      "header {header:08b}"
+    );
+  }
+}
+
+/// Frames with the same palette indices but different colours differ
+#[test]
+fn recoloured_frames() {
+  let raw = |_| 3;
+  let bitmap = pattern(0);
+  let ink = bitmap.iter().flatten().filter(|&&pixel| pixel == 1).count();
+  let build = |header| {
+    load(build_ppm(
+      &[encode_frame(header, (0, 0), [(&bitmap, &raw), (&blank(), &raw)])],
+      8,
+    ))
+  };
+
+  for (header, changed_pixels) in [
+    (NEW_FRAME, None),
+    // Layer one drawn in red rather than black
+    (0b1001_0101, Some(ink)),
+    // Black paper, so every pixel is recoloured
+    (0b1001_0010, Some(WIDTH * HEIGHT)),
+  ] {
+    let diff =
+      diff::Diff::new(&mut build(NEW_FRAME), &mut build(header), false);
+
+    assert_eq!(
+      diff.frames.first().map(|frame| frame.changed_pixels),
+      changed_pixels,
+      "header {header:08b}"
+    );
+  }
+}
+
+/// Flipnotes with the same frames and colours are duplicates whatever their
+/// metadata, while recoloured or retouched ones are only near duplicates
+#[test]
+fn duplicates() {
+  let raw = |_| 3;
+  let bitmap = pattern(0);
+  let mut retouched = bitmap.clone();
+
+  // A square of ink in one corner
+  for line in &mut retouched[..48] {
+    line[..64].fill(1);
+  }
+
+  let build = |header, bitmap: &Bitmap| {
+    build_ppm(
+      &[encode_frame(header, (0, 0), [(bitmap, &raw), (&blank(), &raw)])],
+      8,
+    )
+  };
+  let mut renamed = build(NEW_FRAME, &bitmap);
+
+  // The current author's name
+  renamed[0x40..0x44].copy_from_slice(&[b'B', 0, b'o', 0]);
+
+  let fingerprints = [
+    build(NEW_FRAME, &bitmap),
+    renamed,
+    // Layer one drawn in red rather than black
+    build(0b1001_0101, &bitmap),
+    build(NEW_FRAME, &retouched),
+    build(NEW_FRAME, &inverted(&bitmap)),
+  ]
+  .into_iter()
+  .enumerate()
+  .map(|(index, ppm)| {
+    dedupe::Fingerprint::new(&index.to_string(), &mut load(ppm))
+  })
+  .collect::<Vec<_>>();
+  let members = |threshold| {
+    dedupe::group(&fingerprints, threshold)
+      .into_iter()
+      .map(|group| group.members)
+      .collect::<Vec<_>>()
+  };
+
+  assert!(fingerprints[0].exact == fingerprints[1].exact);
+  assert!(fingerprints[0].exact != fingerprints[2].exact);
+  assert_eq!(fingerprints[0].metadata_differences(&fingerprints[1]), [
+    "current_author_name"
+  ]);
+  assert!(
+    (fingerprints[0].distance(&fingerprints[2]) - 0.0).abs() < f64::EPSILON
+  );
+  assert!(
+    (fingerprints[0].distance(&fingerprints[3]) - 2.0).abs() < f64::EPSILON
+  );
+
+  // Below zero bits, only identical frames are grouped
+  assert_eq!(members(-1.0), [vec![0, 1]]);
+  assert_eq!(members(0.0), [vec![0, 1, 2]]);
+  assert_eq!(members(8.0), [vec![0, 1, 2, 3]]);
+  assert_eq!(members(64.0), [vec![0, 1, 2, 3, 4]]);
+}
+
+/// Frames which can't be decoded are substituted in recovery mode, and
+/// reported along with why
+#[test]
+fn recovery() {
+  let raw = |_| 3;
+  let bitmaps = (0..4).map(pattern).collect::<Vec<_>>();
+  let mut frames = bitmaps
+    .iter()
+    .map(|bitmap| {
+      encode_frame(NEW_FRAME, (0, 0), [(bitmap, &raw), (&blank(), &raw)])
+    })
+    .collect::<Vec<_>>();
+
+  // The last frame is cut short
+  let length = frames[3].len() / 2;
+
+  frames[3].truncate(length);
+
+  let mut ppm = build_ppm(&frames, 8);
+
+  // The second frame's offset points past the animation data
+  ppm[0x06AC..0x06B0].copy_from_slice(&0xFFFF_FF00_u32.to_le_bytes());
+
+  let decoded = |index: usize| [layer_pixels(&bitmaps[index], 1), blank()];
+
+  for (recovery, substitutes) in [
+    (ppm::Recovery::Blank, [[blank(), blank()], [blank(), blank()]]),
+    (ppm::Recovery::Repeat, [decoded(0), decoded(2)]),
+  ] {
+    let mut parser = PPMParser::new(ppm.clone());
+    let damaged_frames = parser.load_recovering(recovery).clone();
+
+    assert_eq!(damaged_frames.keys().copied().collect::<Vec<_>>(), [1, 3]);
+    assert!(damaged_frames[&1].starts_with("frame 1's offset"));
+    assert!(damaged_frames[&3].starts_with("frame 3's line data overruns"));
+    assert_eq!(parser.get_frame_count(), 4);
+
+    // In playback order, and then seeking back
+    for index in [0, 1, 2, 3, 1, 0, 3] {
+      let expected = match index {
+        1 => &substitutes[0],
+        3 => &substitutes[1],
+        _ => &decoded(index),
+      };
+
+      assert_eq!(&parser.get_layer_pixels(index), expected, "frame {index}");
+    }
+
+    let frames = parser.dump_json(true)["frames"].clone();
+
+    assert_eq!(
+      frames[1],
+      serde_json::json!({
+        "index": 1,
+        "damaged": damaged_frames[&1],
+      })
+    );
+    assert_eq!(frames[3]["damaged"], damaged_frames[&3]);
+    assert_eq!(frames[2]["damaged"], serde_json::Value::Null);
+  }
+
+  // Opening a damaged file reports the same frames, from which `para`
+  // reports what it salvaged
+  let path = std::env::temp_dir()
+    .join(format!("para-recovery-{}.ppm", std::process::id()));
+
+  std::fs::write(&path, &ppm).unwrap();
+
+  let (flipnote, damaged_frames) =
+    flipnote::open_recovering(&path.to_string_lossy(), ppm::Recovery::Blank)
+      .unwrap();
+
+  std::fs::remove_file(&path).unwrap();
+  assert_eq!(flipnote.get_frame_count(), 4);
+  assert_eq!(
+    damaged_frames,
+    PPMParser::new(ppm).load_recovering(ppm::Recovery::Blank).clone()
+  );
+}
+
+#[test]
+fn speeds() {
+  let empty = |_| 0;
+
+  for speed in 1..=8 {
+    let parser = load(build_ppm(
+      &[encode_frame(NEW_FRAME, (0, 0), [
+        (&blank(), &empty),
+        (&blank(), &empty),
+      ])],
+      speed,
+    ));
+    let metadata = parser.metadata();
+
+    assert_eq!(metadata.frame_speed, speed);
+    assert_eq!(metadata.bgm_speed, u32::from(speed));
+    assert!(
+      (parser.get_framerate() - framerates()[&speed]).abs() < f64::EPSILON
+    );
+    assert!(
+      (metadata.bgm_framerate - framerates()[&speed]).abs() < f64::EPSILON
+    );
+  }
+}
+
+/// The sound header is padded to a multiple of four, whatever the animation
+/// data and sound effect flags leave over
+#[test]
+fn sound_header_padding() {
+  for (animation_size, frame_count, offset) in [
+    (0x0100, 0x20, 0x07C0),
+    (0x0100, 0x21, 0x07C4),
+    (0x0100, 0x22, 0x07C4),
+    (0x0100, 0x23, 0x07C4),
+    (0x0101, 0x24, 0x07C8),
+  ] {
+    assert_eq!(
+      ppm::sound_header_offset(animation_size, frame_count),
+      offset,
+      "{animation_size:#X} bytes of animation data, {frame_count} frames"
+    );
+  }
+}
+
+/// Whatever the animation data and sound effect flags leave over, the sound
+/// header, and so the speeds and the signature, are found after the padding
+#[test]
+fn unaligned_sound_header() {
+  let mut remainders = Vec::new();
+
+  for padding in 0..4 {
+    let frames = [padded_frame(padding)];
+    let ppm = build_signed_ppm(&frames, 3, [&[0x12; 6], &[], &[0x34; 2], &[]]);
+    let animation_size = 8 + 4 + frames[0].len();
+    let sound_header = ppm::sound_header_offset(animation_size as u64, 1);
+    let parser = load(ppm);
+    let signature = parser.get_signature();
+
+    remainders.push((0x06A0 + animation_size + 1) % 4);
+
+    assert_eq!(parser.metadata().frame_speed, 3, "padding {padding}");
+    assert_eq!(signature.offset() as u64, sound_header + 0x20 + 8);
+    assert_eq!(signature.bytes(), Some(&[0xA5; SIGNATURE_SIZE][..]));
+  }
+
+  remainders.sort_unstable();
+
+  assert_eq!(remainders, [0, 1, 2, 3]);
+}
+
+/// `para check` finds the sound header where the parser does, and accepts
+/// the zero `sound_data_size` real files often have
+#[test]
+fn check_sound_header() {
+  for padding in 0..4 {
+    let mut ppm =
+      build_signed_ppm(&[padded_frame(padding)], 8, [&[0x12; 6], &[], &[], &[
+      ]]);
+
+    for sound_size in [6u32, 0] {
+      ppm[0x08..0x0C].copy_from_slice(&sound_size.to_le_bytes());
+
+      let issues = check::check(&ppm)
+        .into_iter()
+        .map(|issue| issue.message)
+        .collect::<Vec<_>>();
+
+      assert!(issues.is_empty(), "padding {padding}: {issues:?}");
+    }
+
+    ppm[0x08..0x0C].copy_from_slice(&4u32.to_le_bytes());
+
+    assert_eq!(
+      check::check(&ppm)
+        .into_iter()
+        .map(|issue| issue.message)
+        .collect::<Vec<_>>(),
+      ["sound_data_size is 4, but the sound tracks total 6 bytes"]
+    );
+  }
+}
+
+/// Sound tracks are read from after the sound header wherever it lands, so
+/// each decodes from its own first byte
+#[test]
+fn unaligned_sound_tracks() {
+  let tracks: [Vec<u8>; 4] = [
+    (0..64).collect(),
+    vec![0x77; 16],
+    (0..32).map(|byte: u8| byte.wrapping_mul(37)).collect(),
+    vec![0x08; 8],
+  ];
+
+  for padding in 0..4 {
+    let parser = load(build_signed_ppm(&[padded_frame(padding)], 8, [
+      &tracks[0], &tracks[1], &tracks[2], &tracks[3],
+    ]));
+
+    for (index, track) in tracks.iter().enumerate() {
+      let samples = parser.decode_sound_track(index);
+
+      assert_eq!(samples.len(), track.len() * 2, "padding {padding}");
+      assert_eq!(
+        samples,
+        adpcm::decode(track),
+        "padding {padding}, track {index}"
+      );
+    }
+  }
+}
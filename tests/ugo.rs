@@ -0,0 +1,176 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Parses a `.ugo` menu built from one of each kind of entry, and checks that
+//! truncated menus fail to load rather than panicking
+//!
+//! <https://github.com/pbsds/hatena-server/wiki/UGO-format>
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+#![allow(clippy::cast_possible_truncation)]
+
+use {
+  base64::{Engine, engine::general_purpose::STANDARD},
+  para_cli::{
+    ppm::{PPMParser, TMB_SIZE},
+    ugo::UGOParser,
+  },
+  std::{fs, path::PathBuf},
+};
+
+/// Labels are stored as Base64-encoded UTF-16LE
+fn label(text: &str) -> String {
+  STANDARD
+    .encode(text.encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>())
+}
+
+/// The `.tmb` of a sample memo, as a menu embeds it
+fn tmb() -> Vec<u8> {
+  let ppm =
+    fs::read(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("ppms/mrjohn.ppm"))
+      .unwrap();
+
+  ppm[..TMB_SIZE as usize].to_vec()
+}
+
+fn build_ugo() -> Vec<u8> {
+  let table = [
+    "0\t2".to_string(),
+    format!("1\t0\t{}\t{}", label("Top"), label("")),
+    format!("2\thttp://example/category\t{}\t1", label("<Hot>")),
+    format!("3\thttp://example/post\t{}", label("Post")),
+    format!("4\thttp://example/memo.ppm\t3\t{}\t765\t0", label("Memo")),
+    format!("4\thttp://example/next.uls\t100\t{}", label("Next")),
+    "9\tunknown".to_string(),
+  ]
+  .join("\n")
+  .into_bytes();
+  let mut ugo = b"UGAR".to_vec();
+
+  ugo.extend(2_u32.to_le_bytes());
+  ugo.extend((table.len() as u32).to_le_bytes());
+  ugo.extend((TMB_SIZE as u32).to_le_bytes());
+  ugo.extend(table);
+  ugo.extend(tmb());
+
+  ugo
+}
+
+fn load(bytes: Vec<u8>) -> UGOParser {
+  let mut parser = UGOParser::new(bytes);
+
+  parser.load().unwrap();
+
+  parser
+}
+
+#[test]
+fn sections() {
+  let json = load(build_ugo()).to_json();
+  let items = json["items"].as_array().unwrap();
+  let mut parser = PPMParser::new(tmb());
+
+  parser.load_metadata();
+
+  assert_eq!(items.len(), 7);
+  assert_eq!(items[0], serde_json::json!({ "type": "layout", "values": [2] }));
+  assert_eq!(
+    items[1],
+    serde_json::json!({
+      "type": "top_screen",
+      "kind": 0,
+      "labels": ["Top", ""],
+    })
+  );
+  assert_eq!(
+    items[2],
+    serde_json::json!({
+      "type": "category",
+      "url": "http://example/category",
+      "label": "<Hot>",
+      "selected": true,
+    })
+  );
+  assert_eq!(
+    items[3],
+    serde_json::json!({
+      "type": "post",
+      "url": "http://example/post",
+      "label": "Post",
+    })
+  );
+
+  // Only buttons linking to a Flipnote have a `.tmb` embedded
+  assert_eq!(items[4]["url"], "http://example/memo.ppm");
+  assert_eq!(items[4]["icon"], 3);
+  assert_eq!(items[4]["label"], "Memo");
+  assert_eq!(items[4]["extra"], serde_json::json!(["765", "0"]));
+  assert_eq!(items[4]["tmb"]["metadata"], parser.header_json());
+  assert!(
+    items[4]["tmb"]["thumbnail"]
+      .as_str()
+      .unwrap()
+      .starts_with("data:image/png;base64,")
+  );
+  assert_eq!(items[5]["label"], "Next");
+  assert_eq!(items[5]["tmb"], serde_json::Value::Null);
+  assert_eq!(
+    items[6],
+    serde_json::json!({ "type": "unknown", "fields": ["9", "unknown"] })
+  );
+}
+
+#[test]
+fn html() {
+  let html = load(build_ugo()).to_html();
+
+  assert!(html.contains("<header>\n<p>Top</p>\n</header>"));
+  assert!(html.contains(
+    "<a href=\"http://example/category\" class=\"selected\">&lt;Hot&gt;</a>"
+  ));
+  assert!(html.contains(
+    "<form method=\"post\" action=\"http://example/post\"><button>Post</button>"
+  ));
+  assert!(html.contains("<main class=\"layout-2\">"));
+  assert!(html.contains(
+    "<a href=\"http://example/memo.ppm\"><img src=\"data:image/png;base64,"
+  ));
+  assert!(html.contains(
+    "<a href=\"http://example/next.uls\"><span>Next</span><small></small></a>"
+  ));
+}
+
+#[test]
+fn truncated() {
+  let ugo = build_ugo();
+
+  for length in 0..ugo.len() {
+    assert!(UGOParser::new(ugo[..length].to_vec()).load().is_err());
+  }
+}
+
+#[test]
+fn oversized_section() {
+  let mut ugo = build_ugo();
+  let remaining = ugo.len() - 16;
+
+  // Claims a table of contents far larger than the menu
+  ugo[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+
+  assert_eq!(
+    UGOParser::new(ugo).load().err().unwrap(),
+    format!(
+      "truncated table of contents: expected {} bytes, found {}",
+      u32::MAX,
+      remaining
+    )
+  );
+}
@@ -0,0 +1,44 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! Walks a directory tree holding symbolic links, including one back up to
+//! its root
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+
+use {para_cli::flipnote::walk, std::fs};
+
+#[cfg(unix)]
+#[test]
+fn symlinks() {
+  use std::os::unix::fs::symlink;
+
+  let root =
+    std::env::temp_dir().join(format!("para-walk-{}", std::process::id()));
+  let nested = root.join("nested");
+
+  let _ = fs::remove_dir_all(&root);
+  fs::create_dir_all(&nested).unwrap();
+  fs::write(root.join("a.ppm"), b"").unwrap();
+  fs::write(nested.join("b.ppm"), b"").unwrap();
+  symlink(&root, nested.join("loop")).unwrap();
+  symlink(root.join("a.ppm"), nested.join("c.ppm")).unwrap();
+  symlink(root.join("missing.ppm"), nested.join("dangling.ppm")).unwrap();
+
+  let paths = walk(&root);
+
+  fs::remove_dir_all(&root).unwrap();
+  assert_eq!(paths, [
+    root.join("a.ppm"),
+    nested.join("b.ppm"),
+    nested.join("c.ppm")
+  ]);
+}
@@ -0,0 +1,146 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: MIT
+
+//! JavaScript bindings to the decoder behind `para`, so that Flipnotes can
+//! be played in a browser
+//!
+//! ```js
+//! import init, { Flipnote } from "./pkg/para_wasm.js";
+//!
+//! await init();
+//!
+//! const flipnote = new Flipnote(new Uint8Array(await response.arrayBuffer()));
+//! const frame = new ImageData(flipnote.frame(0), flipnote.width);
+//! const bgm = flipnote.soundTrack("bgm"); // Float32Array at sampleRate
+//! ```
+
+#![deny(
+  warnings,
+  nonstandard_style,
+  unused,
+  future_incompatible,
+  rust_2018_idioms,
+  unsafe_code
+)]
+#![deny(clippy::all, clippy::nursery, clippy::pedantic)]
+#![allow(clippy::must_use_candidate, clippy::missing_errors_doc)]
+
+use {
+  para_cli::{flipnote, palette::Palette},
+  serde::Serialize,
+  wasm_bindgen::{Clamped, prelude::*},
+};
+
+/// A `.ppm` or `.kwz`, detected by its contents
+#[wasm_bindgen]
+pub struct Flipnote {
+  flipnote: Box<dyn flipnote::Flipnote>,
+  palette:  Palette,
+}
+#[wasm_bindgen]
+impl Flipnote {
+  #[wasm_bindgen(constructor)]
+  pub fn new(bytes: &[u8]) -> Result<Self, JsError> {
+    let flipnote = flipnote::from_bytes(bytes.to_vec())
+      .ok_or_else(|| JsError::new("unsupported format, expected a Flipnote"))?;
+    let palette = flipnote.default_palette();
+
+    Ok(Self { flipnote, palette })
+  }
+
+  /// Frame width in pixels
+  #[wasm_bindgen(getter)]
+  pub fn width(&self) -> usize { self.flipnote.dimensions().0 }
+
+  /// Frame height in pixels
+  #[wasm_bindgen(getter)]
+  pub fn height(&self) -> usize { self.flipnote.dimensions().1 }
+
+  #[wasm_bindgen(getter, js_name = frameCount)]
+  pub fn frame_count(&self) -> u16 { self.flipnote.get_frame_count() }
+
+  #[wasm_bindgen(getter, js_name = thumbIndex)]
+  pub fn thumb_index(&self) -> u16 { self.flipnote.get_thumb_index() }
+
+  /// Frames per second
+  #[wasm_bindgen(getter)]
+  pub fn framerate(&self) -> f64 { self.flipnote.get_framerate() }
+
+  /// Playback length in seconds
+  #[wasm_bindgen(getter)]
+  pub fn duration(&self) -> f64 { self.flipnote.get_duration() }
+
+  /// Samples per second of the sound tracks
+  #[wasm_bindgen(getter, js_name = sampleRate)]
+  pub fn sample_rate(&self) -> u32 { self.flipnote.sample_rate() }
+
+  /// Every metadata field, as shown by `para info --json`
+  pub fn metadata(&self) -> Result<JsValue, JsError> {
+    Ok(
+      self
+        .flipnote
+        .info_json()
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())?,
+    )
+  }
+
+  /// Draw frames with a preset, such as `"3ds"`, or with colours given as
+  /// `"black,white,red,blue"` in hexadecimal
+  #[wasm_bindgen(js_name = setPalette)]
+  pub fn set_palette(&mut self, palette: &str) -> Result<(), JsError> {
+    self.palette =
+      Palette::parse(palette).map_err(|error| JsError::new(&error))?;
+
+    Ok(())
+  }
+
+  /// A frame as RGBA pixels, row by row, as `ImageData` takes them; the
+  /// paper is left transparent when `transparent` is set
+  pub fn frame(
+    &mut self,
+    index: usize,
+    transparent: Option<bool>,
+  ) -> Result<Clamped<Vec<u8>>, JsError> {
+    let frame = (&mut *self.flipnote as &mut dyn flipnote::Flipnote)
+      .frames(self.palette, false)
+      .nth(index)
+      .and_then(|mut frames| frames.pop())
+      .ok_or_else(|| JsError::new(&format!("no frame {index}")))?;
+
+    Ok(Clamped(
+      frame.to_image(transparent.unwrap_or(false)).into_rgba8().into_raw(),
+    ))
+  }
+
+  /// Names of the sound tracks, in the order they're stored
+  #[wasm_bindgen(js_name = soundTracks)]
+  pub fn sound_tracks(&self) -> Vec<String> {
+    self
+      .flipnote
+      .sound_tracks()
+      .into_iter()
+      .map(|track| track.name.to_string())
+      .collect()
+  }
+
+  /// A sound track, such as `"bgm"` or `"se1"`, as PCM samples between -1
+  /// and 1 at `sampleRate`, as an `AudioBuffer` takes them
+  #[wasm_bindgen(js_name = soundTrack)]
+  pub fn sound_track(&self, name: &str) -> Result<Vec<f32>, JsError> {
+    let index = self
+      .flipnote
+      .sound_tracks()
+      .iter()
+      .position(|track| track.name == name)
+      .ok_or_else(|| JsError::new(&format!("no sound track {name}")))?;
+
+    Ok(
+      self
+        .flipnote
+        .decode_sound_track(index)
+        .into_iter()
+        .map(|sample| f32::from(sample) / 32768.0)
+        .collect(),
+    )
+  }
+}